@@ -1,13 +1,279 @@
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error, fmt::Write, path::PathBuf};
 
-use spirv_builder::{MetadataPrintout, SpirvBuilder};
+#[allow(dead_code)]
+#[path = "src/reflect.rs"]
+mod reflect;
+#[cfg(all(feature = "shader-toolchain", not(feature = "prebuilt-shaders")))]
+#[path = "src/shader_build.rs"]
+mod shader_build;
+
+#[cfg(not(any(feature = "shader-toolchain", feature = "prebuilt-shaders")))]
+compile_error!("enable either the `shader-toolchain` (default) or the `prebuilt-shaders` feature");
+
+/// Checked-in modules used by the `prebuilt-shaders` feature, with a `manifest.txt` recording
+/// the hash of the shader sources they were built from.
+const PREBUILT_DIR: &str = "shader/prebuilt";
+/// Set to copy the freshly built modules to [`PREBUILT_DIR`].
+#[cfg(all(feature = "shader-toolchain", not(feature = "prebuilt-shaders")))]
+const UPDATE_PREBUILT_VAR: &str = "DOT_UPDATE_PREBUILT_SHADERS";
+/// First line of `manifest.txt`; bump when its format changes.
+const PREBUILT_FORMAT: &str = "dot prebuilt shaders 1";
 
 fn main() -> Result<(), Box<dyn Error>> {
-	SpirvBuilder::new("shader", "spirv-unknown-vulkan1.0")
-		.capability(spirv_builder::Capability::StorageImageWriteWithoutFormat)
-		.extension("SPV_KHR_storage_buffer_storage_class")
-		.release(true)
-		.print_metadata(MetadataPrintout::Full)
-		.build()?;
+	let modules = modules()?;
+	let manifest = generate_manifest(&modules)?;
+	let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+	std::fs::write(out_dir.join("shaders.rs"), manifest)?;
 	Ok(())
-}
\ No newline at end of file
+}
+
+/// Builds the shader crate with rust-gpu into one module per entry point. Warns if the prebuilt
+/// modules are stale, or replaces them if `DOT_UPDATE_PREBUILT_SHADERS` is set.
+#[cfg(all(feature = "shader-toolchain", not(feature = "prebuilt-shaders")))]
+fn modules() -> Result<BTreeMap<String, PathBuf>, Box<dyn Error>> {
+	let result = shader_build::builder("shader")
+		.print_metadata(spirv_builder::MetadataPrintout::Full)
+		.build()?;
+	let modules = result.module.unwrap_multi().clone();
+
+	println!("cargo:rerun-if-env-changed={UPDATE_PREBUILT_VAR}");
+	let source_hash = source_hash()?;
+	if std::env::var_os(UPDATE_PREBUILT_VAR).is_some() {
+		let _ = std::fs::remove_dir_all(PREBUILT_DIR);
+		std::fs::create_dir_all(PREBUILT_DIR)?;
+		for (name, path) in &modules {
+			std::fs::copy(path, format!("{PREBUILT_DIR}/{name}.spv"))?;
+		}
+		std::fs::write(
+			format!("{PREBUILT_DIR}/manifest.txt"),
+			format!("{PREBUILT_FORMAT}\nsource-hash {source_hash:016x}\n"),
+		)?;
+	} else {
+		match prebuilt_source_hash() {
+			Ok(hash) if hash == source_hash => (),
+			Ok(_) => println!(
+				"cargo:warning={PREBUILT_DIR} is out of date with the shader sources; update it \
+				 with scripts/prebuilt-shaders.sh"
+			),
+			Err(e) => println!("cargo:warning={e}; generate it with scripts/prebuilt-shaders.sh"),
+		}
+	}
+	Ok(modules)
+}
+
+/// Uses the checked-in modules in [`PREBUILT_DIR`], after checking that they were built from the
+/// current shader sources.
+#[cfg(feature = "prebuilt-shaders")]
+fn modules() -> Result<BTreeMap<String, PathBuf>, Box<dyn Error>> {
+	println!("cargo:rerun-if-changed=shader/Cargo.toml");
+	println!("cargo:rerun-if-changed=shader/src");
+	println!("cargo:rerun-if-changed={PREBUILT_DIR}");
+
+	let expected = prebuilt_source_hash()
+		.map_err(|e| format!("{e}; generate it with scripts/prebuilt-shaders.sh"))?;
+	let actual = source_hash()?;
+	if expected != actual {
+		return Err(format!(
+			"{PREBUILT_DIR} was built from other shader sources (hash {expected:016x}, sources \
+			 hash {actual:016x}); update it with scripts/prebuilt-shaders.sh"
+		)
+		.into());
+	}
+
+	let mut modules = BTreeMap::new();
+	for entry in std::fs::read_dir(PREBUILT_DIR)? {
+		let path = std::fs::canonicalize(entry?.path())?;
+		if path.extension().is_some_and(|extension| extension == "spv") {
+			let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+			modules.insert(name, path);
+		}
+	}
+	Ok(modules)
+}
+
+/// Reads the source hash from the prebuilt `manifest.txt`.
+fn prebuilt_source_hash() -> Result<u64, Box<dyn Error>> {
+	let path = format!("{PREBUILT_DIR}/manifest.txt");
+	let manifest =
+		std::fs::read_to_string(&path).map_err(|e| format!("cannot read {path}: {e}"))?;
+	let mut lines = manifest.lines();
+	if lines.next() != Some(PREBUILT_FORMAT) {
+		return Err(format!("{path} is not in the format {PREBUILT_FORMAT:?}").into());
+	}
+	lines
+		.find_map(|line| line.strip_prefix("source-hash "))
+		.and_then(|hash| u64::from_str_radix(hash, 16).ok())
+		.ok_or_else(|| format!("{path} has no source-hash line").into())
+}
+
+/// FNV-1a over the shader crate's manifest and sources, with paths relative to the crate and
+/// line endings normalized, so it is the same on every platform and Rust version.
+fn source_hash() -> Result<u64, Box<dyn Error>> {
+	let mut files = vec![PathBuf::from("Cargo.toml")];
+	let mut directories = vec![PathBuf::from("src")];
+	while let Some(directory) = directories.pop() {
+		for entry in std::fs::read_dir(PathBuf::from("shader").join(&directory))? {
+			let entry = entry?;
+			let path = directory.join(entry.file_name());
+			if entry.file_type()?.is_dir() {
+				directories.push(path);
+			} else {
+				files.push(path);
+			}
+		}
+	}
+	files.sort();
+
+	let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+	let mut feed = |bytes: &[u8]| {
+		for &byte in bytes {
+			hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+		}
+	};
+	for file in files {
+		let relative = file.to_string_lossy().replace('\\', "/");
+		feed(relative.as_bytes());
+		feed(&[0]);
+		let contents = std::fs::read(PathBuf::from("shader").join(&file))?;
+		let contents: Vec<u8> = contents.into_iter().filter(|&byte| byte != b'\r').collect();
+		feed(&contents);
+		feed(&[0]);
+	}
+	Ok(hash)
+}
+
+/// Generates `shaders.rs`, included by `main.rs`: an `EntryPoint` enum with a variant per entry
+/// point, its reflected stage, local size and descriptor bindings, and its embedded module.
+fn generate_manifest(modules: &BTreeMap<String, PathBuf>) -> Result<String, Box<dyn Error>> {
+	struct Entry {
+		name: String,
+		variant: String,
+		path: String,
+		stage: reflect::Stage,
+		local_size: Option<[u32; 3]>,
+		bindings: Vec<reflect::DescriptorBinding>,
+	}
+
+	let mut entries = Vec::new();
+	for (name, path) in modules {
+		let spirv = reflect::load(path).map_err(|e| format!("{}: {e}", path.display()))?;
+		let stage = reflect::entry_points(&spirv)
+			.into_iter()
+			.find(|entry_point| entry_point.name == *name)
+			.ok_or_else(|| format!("{} doesn't declare {name}", path.display()))?
+			.stage;
+		entries.push(Entry {
+			name: name.clone(),
+			variant: name
+				.split('_')
+				.map(|word| {
+					let mut chars = word.chars();
+					chars
+						.next()
+						.map(|first| first.to_uppercase().chain(chars).collect::<String>())
+						.unwrap_or_default()
+				})
+				.collect::<String>(),
+			path: path.to_str().ok_or("non-UTF-8 module path")?.to_owned(),
+			stage,
+			local_size: reflect::local_size(&spirv, name),
+			bindings: reflect::descriptor_bindings(&spirv),
+		});
+	}
+
+	let mut out = String::new();
+	writeln!(
+		out,
+		"// Generated by build.rs from the shader crate's modules."
+	)?;
+	writeln!(out)?;
+	writeln!(
+		out,
+		"/// An entry point of the shader crate. Removing one from the shader removes its variant,"
+	)?;
+	writeln!(out, "/// so code still referring to it stops compiling.")?;
+	writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]")?;
+	writeln!(out, "pub enum EntryPoint {{")?;
+	for entry in &entries {
+		writeln!(out, "\t{},", entry.variant)?;
+	}
+	writeln!(out, "}}")?;
+	writeln!(out)?;
+	writeln!(out, "impl EntryPoint {{")?;
+	writeln!(out, "\tpub const ALL: &'static [Self] = &[")?;
+	for entry in &entries {
+		writeln!(out, "\t\tSelf::{},", entry.variant)?;
+	}
+	writeln!(out, "\t];")?;
+
+	let mut method = |signature: &str, value: &dyn Fn(&Entry) -> String| -> std::fmt::Result {
+		writeln!(out)?;
+		writeln!(out, "\t{signature} {{")?;
+		writeln!(out, "\t\tmatch self {{")?;
+		for entry in &entries {
+			writeln!(out, "\t\t\tSelf::{} => {},", entry.variant, value(entry))?;
+		}
+		writeln!(out, "\t\t}}")?;
+		writeln!(out, "\t}}")
+	};
+	method("pub const fn name(self) -> &'static str", &|entry| {
+		format!("{:?}", entry.name)
+	})?;
+	method(
+		"pub const fn stage(self) -> crate::reflect::Stage",
+		&|entry| format!("crate::reflect::Stage::{:?}", entry.stage),
+	)?;
+	method(
+		"pub const fn local_size(self) -> Option<[u32; 3]>",
+		&|entry| format!("{:?}", entry.local_size),
+	)?;
+	method(
+		"pub const fn bindings(self) -> &'static [crate::reflect::DescriptorBinding]",
+		&|entry| {
+			let bindings: Vec<_> = entry
+				.bindings
+				.iter()
+				.map(|binding| {
+					format!(
+						"crate::reflect::DescriptorBinding {{ set: {}, binding: {}, kind: \
+						 crate::reflect::DescriptorKind::{:?} }}",
+						binding.set, binding.binding, binding.kind
+					)
+				})
+				.collect();
+			format!("&[{}]", bindings.join(", "))
+		},
+	)?;
+	method(
+		"/// The SPIR-V module declaring only this entry point.\n\tpub fn spirv(self) -> &'static \
+		 [u8]",
+		&|entry| format!("include_bytes!({:?})", entry.path),
+	)?;
+	writeln!(out, "}}")?;
+	writeln!(out)?;
+	writeln!(out, "impl std::fmt::Display for EntryPoint {{")?;
+	writeln!(
+		out,
+		"\tfn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{"
+	)?;
+	writeln!(out, "\t\tf.write_str(self.name())")?;
+	writeln!(out, "\t}}")?;
+	writeln!(out, "}}")?;
+	writeln!(out)?;
+	writeln!(out, "/// The modules of every entry point.")?;
+	writeln!(out, "pub fn modules() -> crate::reflect::Modules {{")?;
+	writeln!(
+		out,
+		"\tlet mut modules = crate::reflect::Modules::default();"
+	)?;
+	writeln!(out, "\tfor entry_point in EntryPoint::ALL {{")?;
+	writeln!(
+		out,
+		"\t\tmodules.push(crate::reflect::from_bytes(entry_point.spirv()).expect(\"checked by \
+		 build.rs\"));"
+	)?;
+	writeln!(out, "\t}}")?;
+	writeln!(out, "\tmodules")?;
+	writeln!(out, "}}")?;
+	Ok(out)
+}
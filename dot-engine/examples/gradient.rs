@@ -0,0 +1,73 @@
+//! The animated gradient, drawn into a transient image and then vignetted into the target. F11
+//! toggles borderless fullscreen and V cycles through the present modes.
+//!
+//! `cargo run --example gradient`, or with `-- --headless --screenshot gradient.png` to render
+//! one frame without a window.
+
+use dot::{
+	graph,
+	input::KeyCode,
+	shaders::EntryPoint,
+	window::{PresentMode, WindowMode},
+};
+
+/// The shaders derive everything from the frame parameters, so the only state is what the keys
+/// pressed during the last ticks ask the next frame to change.
+#[derive(Default)]
+struct Gradient {
+	toggle_fullscreen: bool,
+	next_present_mode: bool,
+}
+
+impl dot::App for Gradient {
+	fn init(&mut self, init: dot::Init<'_>) -> Result<(), dot::RendererError> {
+		init.renderer.set_graph(&graph::RenderGraph {
+			passes: vec![
+				graph::Pass {
+					name: "gradient".to_owned(),
+					outputs: vec![(0, graph::ImageRef::Transient("scene".to_owned()))],
+					..graph::Pass::new(EntryPoint::Main)
+				},
+				graph::Pass {
+					inputs: vec![(2, graph::ImageRef::Transient("scene".to_owned()))],
+					outputs: vec![(0, graph::ImageRef::Target)],
+					..graph::Pass::new(EntryPoint::Vignette)
+				},
+			],
+		})
+	}
+
+	fn update(&mut self, update: dot::Update<'_>) {
+		self.toggle_fullscreen |= update.input.key_just_pressed(KeyCode::F11);
+		self.next_present_mode |= update.input.key_just_pressed(KeyCode::KeyV);
+	}
+
+	fn render(&mut self, frame: dot::Frame<'_>) -> Result<(), dot::RendererError> {
+		if std::mem::take(&mut self.toggle_fullscreen) {
+			if let Some(window) = frame.window {
+				let mut config = window.config().clone();
+				config.mode = match config.mode {
+					WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+					_ => WindowMode::Windowed,
+				};
+				if let Err(e) = window.set_config(config) {
+					println!("failed to toggle fullscreen: {}", dot::report(&e));
+				}
+			}
+		}
+		if std::mem::take(&mut self.next_present_mode) {
+			let present_mode = match frame.renderer.present_mode() {
+				PresentMode::Fifo => PresentMode::Mailbox,
+				PresentMode::Mailbox => PresentMode::Immediate,
+				PresentMode::Immediate => PresentMode::Fifo,
+			};
+			println!("Present mode {present_mode:?}");
+			frame.renderer.set_present_mode(present_mode);
+		}
+		frame.renderer.run(frame.image_extent, None)
+	}
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+	dot::run(Gradient::default(), dot::Config::from_args()?)
+}
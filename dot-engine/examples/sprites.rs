@@ -0,0 +1,85 @@
+//! A ring of spinning sprites over the gradient. The entities live in the engine's ECS world:
+//! the built-in systems turn them by their velocity every tick and extract them into the buffer
+//! the `sprites` pass draws. The `reverse` action, Space, the left mouse button or the south
+//! gamepad button unless rebound with `--bindings PATH`, reverses their spin.
+//!
+//! `cargo run --example sprites`
+
+use dot::{
+	ecs::{
+		default_world::*, prelude::*, PreviousTransform, Sprite, Transform, Velocity, SPRITE_BUFFER,
+	},
+	gamepad::GamepadButton,
+	glam::{vec2, vec4},
+	graph,
+	input::{Binding, KeyCode, MouseButton},
+	shaders::EntryPoint,
+};
+
+const SPRITE_COUNT: u32 = 8;
+const REVERSE: &str = "reverse";
+
+struct Sprites;
+
+impl dot::App for Sprites {
+	fn init(&mut self, init: dot::Init<'_>) -> Result<(), dot::RendererError> {
+		if init.input.actions.bindings(REVERSE).is_empty() {
+			init.input
+				.actions
+				.bind(REVERSE, Binding::Key(KeyCode::Space))
+				.bind(REVERSE, Binding::Mouse(MouseButton::Left))
+				.bind(REVERSE, Binding::Gamepad(GamepadButton::South));
+		}
+
+		for i in 0..SPRITE_COUNT {
+			let angle = i as f32 * std::f32::consts::TAU / SPRITE_COUNT as f32;
+			let transform = Transform::from_position(
+				vec2(400.0, 300.0) + vec2(angle.cos(), angle.sin()) * 200.0,
+			);
+			init.world.create::<MovingSprite>((
+				transform,
+				PreviousTransform(transform),
+				Velocity {
+					angular: 1.0 + i as f32 * 0.25,
+					..Default::default()
+				},
+				Sprite {
+					size: vec2(48.0, 48.0),
+					color: vec4(1.0, 1.0, 1.0, 0.8),
+				},
+			));
+		}
+
+		init.renderer.set_graph(&graph::RenderGraph {
+			passes: vec![
+				graph::Pass {
+					name: "gradient".to_owned(),
+					outputs: vec![(0, graph::ImageRef::Transient("scene".to_owned()))],
+					..graph::Pass::new(EntryPoint::Main)
+				},
+				graph::Pass {
+					inputs: vec![(2, graph::ImageRef::Transient("scene".to_owned()))],
+					outputs: vec![(0, graph::ImageRef::Target)],
+					extracted_buffers: vec![(3, SPRITE_BUFFER.to_owned())],
+					..graph::Pass::new(EntryPoint::Sprites)
+				},
+			],
+		})
+	}
+
+	fn update(&mut self, update: dot::Update<'_>) {
+		if update.input.action_just_pressed(REVERSE) {
+			ecs_iter!(update.world, |velocity: &mut Velocity| {
+				velocity.angular = -velocity.angular;
+			});
+		}
+	}
+
+	fn render(&mut self, frame: dot::Frame<'_>) -> Result<(), dot::RendererError> {
+		frame.renderer.run(frame.image_extent, None)
+	}
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+	dot::run(Sprites, dot::Config::from_args()?)
+}
@@ -0,0 +1,20 @@
+#!/bin/sh
+# Regenerates the SPIR-V under shader/prebuilt from the shader sources, which needs the rust-gpu
+# toolchain pinned in rust-toolchain.toml. With `check`, builds the engine from the checked-in
+# modules on stable instead, failing if they are missing or out of date; run it in CI.
+set -eu
+cd "$(dirname "$0")/.."
+
+case "${1:-update}" in
+update)
+	DOT_UPDATE_PREBUILT_SHADERS=1 cargo build
+	git status --short shader/prebuilt
+	;;
+check)
+	cargo +stable build --no-default-features --features prebuilt-shaders
+	;;
+*)
+	echo "usage: $0 [update|check]" >&2
+	exit 2
+	;;
+esac
@@ -0,0 +1,96 @@
+//! Host-side emulation of compute dispatches, so kernels can be unit tested with `cargo test`
+//! and compared against what the GPU renders.
+
+use crate::glam::{uvec3, UVec2, UVec3, Vec4};
+
+/// Built-in inputs of a single invocation.
+#[derive(Clone, Copy, Debug)]
+pub struct Invocation {
+	pub num_workgroups: UVec3,
+	pub workgroup_id: UVec3,
+	pub local_invocation_id: UVec3,
+	pub global_invocation_id: UVec3,
+}
+
+/// Runs `kernel` for every invocation of a dispatch of `num_workgroups` groups of `local_size`
+/// threads, one after another in x, y, z order.
+pub fn dispatch(num_workgroups: UVec3, local_size: UVec3, mut kernel: impl FnMut(Invocation)) {
+	for group_z in 0..num_workgroups.z {
+		for group_y in 0..num_workgroups.y {
+			for group_x in 0..num_workgroups.x {
+				let workgroup_id = uvec3(group_x, group_y, group_z);
+				for local_z in 0..local_size.z {
+					for local_y in 0..local_size.y {
+						for local_x in 0..local_size.x {
+							let local_invocation_id = uvec3(local_x, local_y, local_z);
+							kernel(Invocation {
+								num_workgroups,
+								workgroup_id,
+								local_invocation_id,
+								global_invocation_id: workgroup_id * local_size
+									+ local_invocation_id,
+							});
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+/// A host-side stand-in for a 2D storage image binding. Writes outside the image are dropped, as
+/// on the GPU.
+pub struct Image {
+	width: u32,
+	height: u32,
+	texels: core::cell::RefCell<Vec<Vec4>>,
+}
+
+impl Image {
+	pub fn new(width: u32, height: u32) -> Self {
+		Self {
+			width,
+			height,
+			texels: core::cell::RefCell::new(vec![Vec4::ZERO; (width * height) as usize]),
+		}
+	}
+
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+
+	pub fn read_texel(&self, coordinate: UVec2) -> Vec4 {
+		self.texels.borrow()[(coordinate.y * self.width + coordinate.x) as usize]
+	}
+
+	/// Converts the texels to RGBA8 the way a `R8G8B8A8_UNORM` storage image stores them.
+	pub fn to_rgba8(&self) -> Vec<u8> {
+		self.texels
+			.borrow()
+			.iter()
+			.flat_map(|texel| {
+				texel
+					.to_array()
+					.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+			})
+			.collect()
+	}
+}
+
+impl crate::ReadImage2d for Image {
+	fn read_texel(&self, coordinate: UVec2) -> Vec4 {
+		Image::read_texel(self, coordinate)
+	}
+}
+
+impl crate::StorageImage2d for Image {
+	fn write_texel(&self, coordinate: UVec2, texel: Vec4) {
+		if coordinate.x < self.width && coordinate.y < self.height {
+			self.texels.borrow_mut()[(coordinate.y * self.width + coordinate.x) as usize] = texel;
+		}
+	}
+}
@@ -1,24 +1,213 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
-use spirv_std::{
-	glam::{vec3, UVec3, Vec3Swizzles},
-	spirv,
-};
+#[cfg(target_arch = "spirv")]
+use spirv_std::{num_traits::Float, spirv};
 
-#[spirv(compute(threads(1, 1)))]
+#[cfg(not(target_arch = "spirv"))]
+pub use ::glam;
+#[cfg(target_arch = "spirv")]
+pub use spirv_std::glam;
+
+use self::glam::{vec2, vec4, UVec2, UVec3, Vec2, Vec3Swizzles, Vec4, Vec4Swizzles};
+
+#[cfg(not(target_arch = "spirv"))]
+pub mod cpu;
+
+/// Per-frame inputs, filled by the renderer before every dispatch and bound as a storage buffer
+/// at `descriptor_set = 0, binding = 1`. Laid out to match std430.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct FrameParams {
+	/// Size of the output image in pixels.
+	pub resolution: UVec2,
+	/// Cursor position in pixels from the top left corner.
+	pub mouse: Vec2,
+	/// Seconds since the renderer was created.
+	pub time: f32,
+	/// Seconds since the previous frame.
+	pub delta_time: f32,
+	/// Number of frames rendered before this one.
+	pub frame: u32,
+	pub _padding: u32,
+}
+
+#[cfg(not(target_arch = "spirv"))]
+unsafe impl bytemuck::Zeroable for FrameParams {}
+
+#[cfg(not(target_arch = "spirv"))]
+unsafe impl bytemuck::Pod for FrameParams {}
+
+/// A rotated rectangle drawn by `sprites`, extracted from the engine's ECS each frame and bound
+/// as a storage buffer array at `descriptor_set = 0, binding = 3`. Laid out to match std430.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct SpriteInstance {
+	/// Center in pixels from the top left corner.
+	pub position: Vec2,
+	/// Width and height in pixels.
+	pub size: Vec2,
+	/// Blended over the image by its alpha.
+	pub color: Vec4,
+	/// Radians, clockwise on screen.
+	pub rotation: f32,
+	pub _padding: [f32; 3],
+}
+
+#[cfg(not(target_arch = "spirv"))]
+unsafe impl bytemuck::Zeroable for SpriteInstance {}
+
+#[cfg(not(target_arch = "spirv"))]
+unsafe impl bytemuck::Pod for SpriteInstance {}
+
+/// A 2D storage image a kernel writes to. Implemented by the GPU image binding and by
+/// [`cpu::Image`], so kernels can run on either side.
+pub trait StorageImage2d {
+	fn write_texel(&self, coordinate: UVec2, texel: Vec4);
+}
+
+#[cfg(target_arch = "spirv")]
+impl StorageImage2d for spirv_std::Image!(2D, type=f32, sampled=false, depth=false) {
+	fn write_texel(&self, coordinate: UVec2, texel: Vec4) {
+		unsafe {
+			self.write(coordinate, texel);
+		}
+	}
+}
+
+/// A 2D storage image a kernel reads from, e.g. the output of an earlier render graph pass.
+pub trait ReadImage2d {
+	fn read_texel(&self, coordinate: UVec2) -> Vec4;
+}
+
+#[cfg(target_arch = "spirv")]
+impl ReadImage2d for spirv_std::Image!(2D, format = rgba8, sampled = false, depth = false) {
+	fn read_texel(&self, coordinate: UVec2) -> Vec4 {
+		self.read(coordinate)
+	}
+}
+
+#[cfg(target_arch = "spirv")]
+// The renderer reflects the workgroup size from the SPIR-V and dispatches enough groups to cover
+// the image, so kernels must skip invocations outside `params.resolution`.
+#[spirv(compute(threads(8, 8)))]
 pub fn main(
-	#[spirv(num_workgroups)] work_groups: UVec3,
 	#[spirv(global_invocation_id)] id: UVec3,
 	#[spirv(descriptor_set = 0, binding = 0)] image: &spirv_std::Image!(2D, type=f32, sampled=false, depth=false),
+	#[spirv(storage_buffer, descriptor_set = 0, binding = 1)] params: &FrameParams,
 ) {
-	unsafe {
-		image.write(
-			id.xy(),
-			vec3(
-				id.x as f32 / work_groups.x as f32,
-				id.y as f32 / work_groups.y as f32,
-				0.0,
-			),
+	gradient(params, id, image);
+}
+
+/// [`main`] with one invocation per workgroup, the baseline for `dot --bench`.
+#[cfg(target_arch = "spirv")]
+#[spirv(compute(threads(1, 1)))]
+pub fn main_1x1(
+	#[spirv(global_invocation_id)] id: UVec3,
+	#[spirv(descriptor_set = 0, binding = 0)] image: &spirv_std::Image!(2D, type=f32, sampled=false, depth=false),
+	#[spirv(storage_buffer, descriptor_set = 0, binding = 1)] params: &FrameParams,
+) {
+	gradient(params, id, image);
+}
+
+/// Body of `main`: a red/green gradient across the image.
+pub fn gradient(params: &FrameParams, id: UVec3, image: &impl StorageImage2d) {
+	if id.x >= params.resolution.x || id.y >= params.resolution.y {
+		return;
+	}
+	image.write_texel(
+		id.xy(),
+		vec4(
+			id.x as f32 / params.resolution.x as f32,
+			id.y as f32 / params.resolution.y as f32,
+			0.0,
+			1.0,
+		),
+	);
+}
+
+#[cfg(target_arch = "spirv")]
+#[spirv(compute(threads(8, 8)))]
+pub fn vignette(
+	#[spirv(global_invocation_id)] id: UVec3,
+	#[spirv(descriptor_set = 0, binding = 0)] image: &spirv_std::Image!(2D, type=f32, sampled=false, depth=false),
+	#[spirv(storage_buffer, descriptor_set = 0, binding = 1)] params: &FrameParams,
+	#[spirv(descriptor_set = 0, binding = 2)] input: &spirv_std::Image!(
+		2D,
+		format = rgba8,
+		sampled = false,
+		depth = false
+	),
+) {
+	apply_vignette(params, id, input, image);
+}
+
+/// Body of `vignette`: copies `input` to `output`, darkening it towards the corners down to
+/// half brightness.
+pub fn apply_vignette(
+	params: &FrameParams,
+	id: UVec3,
+	input: &impl ReadImage2d,
+	output: &impl StorageImage2d,
+) {
+	if id.x >= params.resolution.x || id.y >= params.resolution.y {
+		return;
+	}
+	let offset = id.xy().as_vec2() / params.resolution.as_vec2() - 0.5;
+	let falloff = (offset.length_squared() * 2.0).min(1.0);
+	let color = input.read_texel(id.xy());
+	output.write_texel(
+		id.xy(),
+		(color.xyz() * (1.0 - 0.5 * falloff)).extend(color.w),
+	);
+}
+
+#[cfg(target_arch = "spirv")]
+#[spirv(compute(threads(8, 8)))]
+pub fn sprites(
+	#[spirv(global_invocation_id)] id: UVec3,
+	#[spirv(descriptor_set = 0, binding = 0)] image: &spirv_std::Image!(2D, type=f32, sampled=false, depth=false),
+	#[spirv(storage_buffer, descriptor_set = 0, binding = 1)] params: &FrameParams,
+	#[spirv(descriptor_set = 0, binding = 2)] input: &spirv_std::Image!(
+		2D,
+		format = rgba8,
+		sampled = false,
+		depth = false
+	),
+	#[spirv(storage_buffer, descriptor_set = 0, binding = 3)] sprites: &[SpriteInstance],
+) {
+	draw_sprites(params, id, sprites, input, image);
+}
+
+/// Body of `sprites`: copies `input` to `output` with `sprites` drawn over it in order.
+pub fn draw_sprites(
+	params: &FrameParams,
+	id: UVec3,
+	sprites: &[SpriteInstance],
+	input: &impl ReadImage2d,
+	output: &impl StorageImage2d,
+) {
+	if id.x >= params.resolution.x || id.y >= params.resolution.y {
+		return;
+	}
+	let center = id.xy().as_vec2() + 0.5;
+	let mut color = input.read_texel(id.xy());
+	let mut i = 0;
+	while i < sprites.len() {
+		let sprite = &sprites[i];
+		// Rotate the texel center into the sprite's frame.
+		let (sin, cos) = sprite.rotation.sin_cos();
+		let offset = center - sprite.position;
+		let local = vec2(
+			offset.x * cos + offset.y * sin,
+			offset.y * cos - offset.x * sin,
 		);
+		if local.abs().cmple(sprite.size * 0.5).all() {
+			color = color
+				.xyz()
+				.lerp(sprite.color.xyz(), sprite.color.w)
+				.extend(color.w);
+		}
+		i += 1;
 	}
-}
\ No newline at end of file
+	output.write_texel(id.xy(), color);
+}
@@ -0,0 +1,107 @@
+use shader::{
+	cpu,
+	glam::{uvec2, uvec3, vec2, vec4, UVec3},
+	FrameParams, SpriteInstance,
+};
+
+#[test]
+fn gradient_spans_the_dispatch_grid() {
+	let image = cpu::Image::new(4, 2);
+	let params = FrameParams {
+		resolution: uvec2(4, 2),
+		..Default::default()
+	};
+	cpu::dispatch(uvec3(4, 2, 1), UVec3::ONE, |invocation| {
+		shader::gradient(&params, invocation.global_invocation_id, &image)
+	});
+
+	assert_eq!(image.read_texel(uvec2(0, 0)), vec4(0.0, 0.0, 0.0, 1.0));
+	assert_eq!(image.read_texel(uvec2(2, 1)), vec4(0.5, 0.5, 0.0, 1.0));
+	assert_eq!(&image.to_rgba8()[4 * 7..], &[191, 128, 0, 255]);
+}
+
+#[test]
+fn gradient_skips_invocations_outside_the_resolution() {
+	let image = cpu::Image::new(8, 8);
+	let params = FrameParams {
+		resolution: uvec2(5, 3),
+		..Default::default()
+	};
+	cpu::dispatch(UVec3::ONE, uvec3(8, 8, 1), |invocation| {
+		shader::gradient(&params, invocation.global_invocation_id, &image)
+	});
+
+	assert_eq!(image.read_texel(uvec2(4, 2)).w, 1.0);
+	assert_eq!(image.read_texel(uvec2(5, 2)), vec4(0.0, 0.0, 0.0, 0.0));
+	assert_eq!(image.read_texel(uvec2(4, 3)), vec4(0.0, 0.0, 0.0, 0.0));
+}
+
+#[test]
+fn vignette_darkens_towards_the_corners() {
+	let input = cpu::Image::new(4, 4);
+	let output = cpu::Image::new(4, 4);
+	let params = FrameParams {
+		resolution: uvec2(4, 4),
+		..Default::default()
+	};
+	cpu::dispatch(uvec3(4, 4, 1), UVec3::ONE, |invocation| {
+		shader::StorageImage2d::write_texel(
+			&input,
+			invocation.global_invocation_id.truncate(),
+			vec4(1.0, 1.0, 1.0, 1.0),
+		);
+	});
+	cpu::dispatch(UVec3::ONE, uvec3(8, 8, 1), |invocation| {
+		shader::apply_vignette(&params, invocation.global_invocation_id, &input, &output)
+	});
+
+	assert_eq!(output.read_texel(uvec2(2, 2)), vec4(1.0, 1.0, 1.0, 1.0));
+	assert_eq!(output.read_texel(uvec2(0, 0)), vec4(0.5, 0.5, 0.5, 1.0));
+}
+
+#[test]
+fn sprites_are_rotated_and_blended_over_the_input() {
+	let input = cpu::Image::new(8, 8);
+	let output = cpu::Image::new(8, 8);
+	let params = FrameParams {
+		resolution: uvec2(8, 8),
+		..Default::default()
+	};
+	let sprites = [
+		SpriteInstance {
+			position: vec2(4.0, 4.0),
+			size: vec2(4.0, 2.0),
+			color: vec4(1.0, 0.0, 0.0, 1.0),
+			rotation: core::f32::consts::FRAC_PI_2,
+			..Default::default()
+		},
+		SpriteInstance {
+			position: vec2(1.0, 1.0),
+			size: vec2(2.0, 2.0),
+			color: vec4(0.0, 0.0, 1.0, 0.5),
+			..Default::default()
+		},
+	];
+	cpu::dispatch(uvec3(8, 8, 1), UVec3::ONE, |invocation| {
+		shader::StorageImage2d::write_texel(
+			&input,
+			invocation.global_invocation_id.truncate(),
+			vec4(0.0, 0.0, 0.0, 1.0),
+		);
+	});
+	cpu::dispatch(UVec3::ONE, uvec3(8, 8, 1), |invocation| {
+		shader::draw_sprites(
+			&params,
+			invocation.global_invocation_id,
+			&sprites,
+			&input,
+			&output,
+		)
+	});
+
+	// Rotated a quarter turn, the 4x2 sprite covers x 3..5 and y 2..6.
+	assert_eq!(output.read_texel(uvec2(4, 5)), vec4(1.0, 0.0, 0.0, 1.0));
+	assert_eq!(output.read_texel(uvec2(5, 4)), vec4(0.0, 0.0, 0.0, 1.0));
+	assert_eq!(output.read_texel(uvec2(0, 0)), vec4(0.0, 0.0, 0.5, 1.0));
+	assert_eq!(output.read_texel(uvec2(2, 2)), vec4(0.0, 0.0, 0.0, 1.0));
+}
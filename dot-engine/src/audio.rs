@@ -0,0 +1,782 @@
+//! Sound playback on rodio: fire-and-forget one-shots, looping music with crossfades, volume
+//! buses and sounds positioned at ECS entities.
+//!
+//! Every playing sound has its own sink, whose volume is the product of the master volume, its
+//! bus's volume and its own gain, reapplied whenever one of them changes. Fades are advanced by
+//! [`Audio::update`], which [`crate::run`] calls once per frame. It also looks up the
+//! [`crate::ecs::Transform`] of each positioned sound's emitter and of the listener, and pans and
+//! attenuates the sound for where the emitter is relative to the listener. With the
+//! [`AudioOutput::Null`] backend sinks play into queues that `update` drains at the rate they
+//! would be played, so sounds start, loop and finish as with a device but without one.
+
+use crate::ecs::prelude::EntityAny;
+use shader::glam::Vec2;
+
+/// Decoded audio, cheap to clone and play any number of times at once.
+#[derive(Clone)]
+pub struct Sound {
+	channels: u16,
+	sample_rate: u32,
+	samples: std::sync::Arc<[f32]>,
+}
+
+impl Sound {
+	/// Reads and decodes a WAV or Ogg Vorbis file.
+	pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, AudioError> {
+		Self::from_bytes(std::fs::read(path)?)
+	}
+
+	/// Decodes a WAV or Ogg Vorbis file's contents.
+	pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, AudioError> {
+		use rodio::Source;
+
+		let decoder = rodio::Decoder::new(std::io::Cursor::new(bytes))?;
+		let channels = decoder.channels();
+		let sample_rate = decoder.sample_rate();
+		Ok(Self::from_samples(
+			channels,
+			sample_rate,
+			decoder.convert_samples().collect(),
+		))
+	}
+
+	/// Interleaved samples of `channels` channels, e.g. generated in code.
+	pub fn from_samples(channels: u16, sample_rate: u32, samples: Vec<f32>) -> Self {
+		Self {
+			channels,
+			sample_rate,
+			samples: samples.into(),
+		}
+	}
+
+	/// Zero for sounds without channels or with a zero sample rate.
+	pub fn duration(&self) -> std::time::Duration {
+		if self.channels == 0 || self.sample_rate == 0 {
+			return std::time::Duration::ZERO;
+		}
+		std::time::Duration::from_secs_f64(
+			self.samples.len() as f64 / self.channels as f64 / self.sample_rate as f64,
+		)
+	}
+
+	fn source(&self, looping: bool) -> SoundSource {
+		SoundSource {
+			sound: self.clone(),
+			position: 0,
+			looping,
+		}
+	}
+}
+
+/// Plays a [`Sound`] once or repeatedly.
+struct SoundSource {
+	sound: Sound,
+	position: usize,
+	looping: bool,
+}
+
+impl Iterator for SoundSource {
+	type Item = f32;
+
+	fn next(&mut self) -> Option<f32> {
+		if self.position == self.sound.samples.len() && self.looping {
+			self.position = 0;
+		}
+		let sample = self.sound.samples.get(self.position).copied()?;
+		self.position += 1;
+		Some(sample)
+	}
+}
+
+impl rodio::Source for SoundSource {
+	fn current_frame_len(&self) -> Option<usize> {
+		None
+	}
+
+	fn channels(&self) -> u16 {
+		self.sound.channels
+	}
+
+	fn sample_rate(&self) -> u32 {
+		self.sound.sample_rate
+	}
+
+	fn total_duration(&self) -> Option<std::time::Duration> {
+		(!self.looping).then(|| self.sound.duration())
+	}
+}
+
+/// Stereo output of a source, panned and attenuated by gains updated from the main thread.
+struct Spatial<S> {
+	input: S,
+	gains: std::sync::Arc<SpatialGains>,
+	/// The right sample of the current frame, once the left one has been returned.
+	right: Option<f32>,
+}
+
+/// Left and right gains of a [`Spatial`] source, as `f32` bits.
+#[derive(Default)]
+struct SpatialGains([std::sync::atomic::AtomicU32; 2]);
+
+impl SpatialGains {
+	fn get(&self) -> [f32; 2] {
+		let [left, right] = &self.0;
+		[left, right].map(|gain| f32::from_bits(gain.load(std::sync::atomic::Ordering::Relaxed)))
+	}
+
+	fn set(&self, gains: [f32; 2]) {
+		for (gain, value) in self.0.iter().zip(gains) {
+			gain.store(value.to_bits(), std::sync::atomic::Ordering::Relaxed);
+		}
+	}
+}
+
+impl<S: rodio::Source<Item = f32>> Iterator for Spatial<S> {
+	type Item = f32;
+
+	fn next(&mut self) -> Option<f32> {
+		if let Some(right) = self.right.take() {
+			return Some(right);
+		}
+		// Mono is played on both sides, anything beyond stereo is downmixed to mono.
+		let (left, right) = match self.input.channels() {
+			1 => {
+				let sample = self.input.next()?;
+				(sample, sample)
+			},
+			2 => (self.input.next()?, self.input.next()?),
+			channels => {
+				let mut sum = 0.0;
+				for _ in 0..channels {
+					sum += self.input.next()?;
+				}
+				let sample = sum / channels as f32;
+				(sample, sample)
+			},
+		};
+		let [left_gain, right_gain] = self.gains.get();
+		self.right = Some(right * right_gain);
+		Some(left * left_gain)
+	}
+}
+
+impl<S: rodio::Source<Item = f32>> rodio::Source for Spatial<S> {
+	fn current_frame_len(&self) -> Option<usize> {
+		None
+	}
+
+	fn channels(&self) -> u16 {
+		2
+	}
+
+	fn sample_rate(&self) -> u32 {
+		self.input.sample_rate()
+	}
+
+	fn total_duration(&self) -> Option<std::time::Duration> {
+		self.input.total_duration()
+	}
+}
+
+/// How positioned sounds fade with distance from the listener, in pixels. Checked by
+/// [`Audio::set_attenuation`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+	/// Sounds closer than this play at full volume and are panned less the closer they are. At
+	/// least zero.
+	pub reference_distance: f32,
+	/// Sounds fade out linearly from the reference distance to silence at this distance. Greater
+	/// than the reference distance.
+	pub max_distance: f32,
+}
+
+impl Default for Attenuation {
+	fn default() -> Self {
+		Self {
+			reference_distance: 100.0,
+			max_distance: 1000.0,
+		}
+	}
+}
+
+impl Attenuation {
+	fn is_valid(&self) -> bool {
+		self.reference_distance.is_finite()
+			&& self.reference_distance >= 0.0
+			&& self.max_distance.is_finite()
+			&& self.max_distance > self.reference_distance
+	}
+
+	/// Left and right gains for a sound at `offset` from the listener. The pan law keeps a
+	/// centered sound at `[1, 1]`, the gains of sounds played without a position, and turns the
+	/// far side down as the sound moves to one side.
+	fn gains(&self, offset: Vec2) -> [f32; 2] {
+		let distance = offset.length();
+		let volume = if distance <= self.reference_distance {
+			1.0
+		} else {
+			(1.0 - (distance - self.reference_distance)
+				/ (self.max_distance - self.reference_distance))
+				.max(0.0)
+		};
+		let pan = if distance > 0.0 {
+			offset.x / distance.max(self.reference_distance)
+		} else {
+			0.0
+		};
+		let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+		[angle.cos(), angle.sin()].map(|gain| (gain * std::f32::consts::SQRT_2).min(1.0) * volume)
+	}
+}
+
+/// Identifies a looping sound started with [`Audio::play_looping_at`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundHandle(u64);
+
+/// Where sounds are played.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AudioOutput {
+	/// The default output device, or [`Self::Null`] if there is none.
+	#[default]
+	Device,
+	/// No device: sounds play silently, in step with [`Audio::update`].
+	Null,
+}
+
+/// A volume shared by a group of sounds, scaled by the master volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bus {
+	Music,
+	Sfx,
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+	Io(std::io::Error),
+	/// The data isn't in a supported format or is corrupt.
+	Decode(rodio::decoder::DecoderError),
+	/// Opening the output device failed.
+	Stream(rodio::StreamError),
+	/// Creating a sink on the output device failed.
+	Play(rodio::PlayError),
+	/// The reference distance is negative or not below the max distance.
+	Attenuation(Attenuation),
+}
+
+impl std::fmt::Display for AudioError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(_) => write!(f, "failed to read the sound"),
+			Self::Decode(_) => write!(f, "failed to decode the sound"),
+			Self::Stream(_) => write!(f, "failed to open the audio output device"),
+			Self::Play(_) => write!(f, "failed to start playing on the audio output device"),
+			Self::Attenuation(attenuation) => write!(
+				f,
+				"invalid attenuation: the reference distance {} must be at least 0 and less than \
+				 the max distance {}",
+				attenuation.reference_distance, attenuation.max_distance,
+			),
+		}
+	}
+}
+
+impl std::error::Error for AudioError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Decode(e) => Some(e),
+			Self::Stream(e) => Some(e),
+			Self::Play(e) => Some(e),
+			Self::Attenuation(_) => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for AudioError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+impl From<rodio::decoder::DecoderError> for AudioError {
+	fn from(e: rodio::decoder::DecoderError) -> Self {
+		Self::Decode(e)
+	}
+}
+
+impl From<rodio::StreamError> for AudioError {
+	fn from(e: rodio::StreamError) -> Self {
+		Self::Stream(e)
+	}
+}
+
+impl From<rodio::PlayError> for AudioError {
+	fn from(e: rodio::PlayError) -> Self {
+		Self::Play(e)
+	}
+}
+
+enum Backend {
+	Device {
+		// Playback stops when the stream is dropped.
+		_stream: rodio::OutputStream,
+		handle: rodio::OutputStreamHandle,
+	},
+	Null,
+}
+
+/// The entity a positioned sound follows.
+struct Emitter {
+	entity: EntityAny,
+	/// Where the entity was last seen; the sound stays there once it is destroyed.
+	position: Option<Vec2>,
+	/// Stop the sound when the entity is destroyed rather than letting it finish.
+	stop_with_entity: bool,
+	gains: std::sync::Arc<SpatialGains>,
+}
+
+/// A sound being played on its own sink.
+struct Voice {
+	/// For [`SoundHandle`]s.
+	id: u64,
+	sink: rodio::Sink,
+	/// With the null backend, the sink's output, drained by [`Audio::update`], and the samples
+	/// per second it plays. The queue only reports the format of the source it is playing, which
+	/// is a placeholder until the first sample is drained.
+	queue: Option<(rodio::queue::SourcesQueueOutput<f32>, f64)>,
+	bus: Bus,
+	/// Volume before the bus and master volumes, moved towards `target_gain` by fades.
+	gain: f32,
+	target_gain: f32,
+	/// Change in gain per second while fading.
+	fade_rate: f32,
+	/// Dropped once faded out.
+	stopping: bool,
+	emitter: Option<Emitter>,
+}
+
+impl Voice {
+	fn fade_to(&mut self, target_gain: f32, duration: std::time::Duration) {
+		self.target_gain = target_gain;
+		if duration.is_zero() {
+			self.gain = target_gain;
+		} else {
+			self.fade_rate = (target_gain - self.gain).abs() / duration.as_secs_f32();
+		}
+	}
+
+	fn advance_fade(&mut self, delta: std::time::Duration) {
+		let step = self.fade_rate * delta.as_secs_f32();
+		self.gain = if self.gain < self.target_gain {
+			(self.gain + step).min(self.target_gain)
+		} else {
+			(self.gain - step).max(self.target_gain)
+		};
+	}
+}
+
+/// The engine's audio subsystem.
+pub struct Audio {
+	backend: Backend,
+	master_volume: f32,
+	music_volume: f32,
+	sfx_volume: f32,
+	/// One-shots, positioned sounds and music fading out.
+	voices: Vec<Voice>,
+	music: Option<Voice>,
+	next_id: u64,
+	listener: Option<EntityAny>,
+	attenuation: Attenuation,
+}
+
+impl Audio {
+	/// Opens `output`, falling back to [`AudioOutput::Null`] with a message if the device can't be
+	/// opened.
+	pub fn new(output: AudioOutput) -> Self {
+		let backend = match output {
+			AudioOutput::Device => match rodio::OutputStream::try_default() {
+				Ok((stream, handle)) => Backend::Device {
+					_stream: stream,
+					handle,
+				},
+				Err(e) => {
+					println!(
+						"{}, continuing without sound",
+						crate::report(&AudioError::from(e))
+					);
+					Backend::Null
+				},
+			},
+			AudioOutput::Null => Backend::Null,
+		};
+		Self {
+			backend,
+			master_volume: 1.0,
+			music_volume: 1.0,
+			sfx_volume: 1.0,
+			voices: Vec::new(),
+			music: None,
+			next_id: 0,
+			listener: None,
+			attenuation: Attenuation::default(),
+		}
+	}
+
+	/// Plays `sound` once on the [`Bus::Sfx`] bus at `volume`.
+	pub fn play(&mut self, sound: &Sound, volume: f32) -> Result<(), AudioError> {
+		let voice = self.start(sound, Bus::Sfx, volume, false, None)?;
+		self.voices.push(voice);
+		Ok(())
+	}
+
+	/// Plays `sound` once on the [`Bus::Sfx`] bus at `volume`, positioned at `emitter` for as long
+	/// as it exists and where it was last seen afterwards. Starts silent until the next
+	/// [`Self::update`] finds the emitter.
+	pub fn play_at(
+		&mut self,
+		sound: &Sound,
+		volume: f32,
+		emitter: impl Into<EntityAny>,
+	) -> Result<(), AudioError> {
+		let voice = self.start(
+			sound,
+			Bus::Sfx,
+			volume,
+			false,
+			Some((emitter.into(), false)),
+		)?;
+		self.voices.push(voice);
+		Ok(())
+	}
+
+	/// Loops `sound` on the [`Bus::Sfx`] bus at `volume`, positioned at `emitter`, until it is
+	/// stopped with [`Self::stop`] or the emitter is destroyed.
+	pub fn play_looping_at(
+		&mut self,
+		sound: &Sound,
+		volume: f32,
+		emitter: impl Into<EntityAny>,
+	) -> Result<SoundHandle, AudioError> {
+		let voice = self.start(sound, Bus::Sfx, volume, true, Some((emitter.into(), true)))?;
+		let handle = SoundHandle(voice.id);
+		self.voices.push(voice);
+		Ok(handle)
+	}
+
+	/// Fades the sound out over `fade`. Does nothing if it has already stopped.
+	pub fn stop(&mut self, sound: SoundHandle, fade: std::time::Duration) {
+		if let Some(voice) = self.voices.iter_mut().find(|voice| voice.id == sound.0) {
+			voice.fade_to(0.0, fade);
+			voice.stopping = true;
+		}
+	}
+
+	/// Sets the entity positioned sounds are heard from. Without one, they play centered at full
+	/// volume.
+	pub fn set_listener(&mut self, listener: Option<EntityAny>) {
+		self.listener = listener;
+	}
+
+	/// Fails, keeping the previous attenuation, if `attenuation` is invalid.
+	pub fn set_attenuation(&mut self, attenuation: Attenuation) -> Result<(), AudioError> {
+		if !attenuation.is_valid() {
+			return Err(AudioError::Attenuation(attenuation));
+		}
+		self.attenuation = attenuation;
+		Ok(())
+	}
+
+	/// Loops `sound` as the music, fading it in over `crossfade` while the previous music fades
+	/// out.
+	pub fn play_music(
+		&mut self,
+		sound: &Sound,
+		crossfade: std::time::Duration,
+	) -> Result<(), AudioError> {
+		self.stop_music(crossfade);
+		let mut voice = self.start(sound, Bus::Music, 0.0, true, None)?;
+		voice.fade_to(1.0, crossfade);
+		self.apply_volume(&voice);
+		self.music = Some(voice);
+		Ok(())
+	}
+
+	/// Fades the current music out over `fade`.
+	pub fn stop_music(&mut self, fade: std::time::Duration) {
+		if let Some(mut music) = self.music.take() {
+			music.fade_to(0.0, fade);
+			music.stopping = true;
+			self.apply_volume(&music);
+			self.voices.push(music);
+		}
+	}
+
+	pub fn master_volume(&self) -> f32 {
+		self.master_volume
+	}
+
+	pub fn set_master_volume(&mut self, volume: f32) {
+		self.master_volume = volume;
+		self.apply_volumes();
+	}
+
+	pub fn bus_volume(&self, bus: Bus) -> f32 {
+		match bus {
+			Bus::Music => self.music_volume,
+			Bus::Sfx => self.sfx_volume,
+		}
+	}
+
+	pub fn set_bus_volume(&mut self, bus: Bus, volume: f32) {
+		match bus {
+			Bus::Music => self.music_volume = volume,
+			Bus::Sfx => self.sfx_volume = volume,
+		}
+		self.apply_volumes();
+	}
+
+	/// Number of sounds playing, including the music and music fading out.
+	pub fn playing(&self) -> usize {
+		self.voices.len() + self.music.iter().len()
+	}
+
+	/// Advances fades by `delta`, pans and attenuates positioned sounds for where their emitters
+	/// are in `world` and drops sounds that have finished or faded out. With the null backend,
+	/// also plays `delta` worth of every sound.
+	pub fn update(&mut self, world: &mut impl crate::ecs::EngineWorld, delta: std::time::Duration) {
+		let listener = self
+			.listener
+			.and_then(|listener| crate::ecs::position(world, listener));
+		for voice in &mut self.voices {
+			let Some(emitter) = &mut voice.emitter else {
+				continue;
+			};
+			let position = crate::ecs::position(world, emitter.entity);
+			let stop = position.is_none() && emitter.stop_with_entity;
+			emitter.position = position.or(emitter.position);
+			let gains = match (emitter.position, listener) {
+				(Some(position), Some(listener)) => self.attenuation.gains(position - listener),
+				(Some(_), None) => [1.0; 2],
+				(None, _) => [0.0; 2],
+			};
+			emitter.gains.set(gains);
+			if stop {
+				voice.fade_to(0.0, std::time::Duration::ZERO);
+				voice.stopping = true;
+			}
+		}
+
+		for voice in self.voices.iter_mut().chain(&mut self.music) {
+			if let Some((queue, samples_per_second)) = &mut voice.queue {
+				let samples = delta.as_secs_f64() * *samples_per_second;
+				queue.by_ref().take(samples as usize).for_each(drop);
+			}
+			voice.advance_fade(delta);
+		}
+		self.apply_volumes();
+		self.voices.retain(|voice| {
+			!(voice.sink.empty() || voice.stopping && voice.gain == voice.target_gain)
+		});
+	}
+
+	/// Creates a sink playing `sound`.
+	fn start(
+		&mut self,
+		sound: &Sound,
+		bus: Bus,
+		gain: f32,
+		looping: bool,
+		emitter: Option<(EntityAny, bool)>,
+	) -> Result<Voice, AudioError> {
+		let channels = match emitter {
+			Some(_) => 2,
+			None => sound.channels,
+		};
+		let (sink, queue) = match &self.backend {
+			Backend::Device { handle, .. } => (rodio::Sink::try_new(handle)?, None),
+			Backend::Null => {
+				let (sink, queue) = rodio::Sink::new_idle();
+				let samples_per_second = sound.sample_rate as f64 * channels as f64;
+				(sink, Some((queue, samples_per_second)))
+			},
+		};
+		let emitter = emitter.map(|(entity, stop_with_entity)| Emitter {
+			entity,
+			position: None,
+			stop_with_entity,
+			gains: Default::default(),
+		});
+		match &emitter {
+			Some(emitter) => sink.append(Spatial {
+				input: sound.source(looping),
+				gains: emitter.gains.clone(),
+				right: None,
+			}),
+			None => sink.append(sound.source(looping)),
+		}
+		self.next_id += 1;
+		let voice = Voice {
+			id: self.next_id,
+			sink,
+			queue,
+			bus,
+			gain,
+			target_gain: gain,
+			fade_rate: 0.0,
+			stopping: false,
+			emitter,
+		};
+		self.apply_volume(&voice);
+		Ok(voice)
+	}
+
+	fn apply_volumes(&self) {
+		for voice in self.voices.iter().chain(&self.music) {
+			self.apply_volume(voice);
+		}
+	}
+
+	fn apply_volume(&self, voice: &Voice) {
+		voice
+			.sink
+			.set_volume(self.master_volume * self.bus_volume(voice.bus) * voice.gain);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use shader::glam::vec2;
+
+	fn silence(seconds: usize) -> Sound {
+		Sound::from_samples(1, 100, vec![0.0; seconds * 100])
+	}
+
+	fn assert_gains(actual: [f32; 2], expected: [f32; 2]) {
+		for (actual, expected) in actual.into_iter().zip(expected) {
+			assert!(
+				(actual - expected).abs() < 1e-5,
+				"{actual} is not {expected}"
+			);
+		}
+	}
+
+	#[test]
+	fn centered_sounds_play_at_the_gains_of_unpositioned_ones() {
+		let attenuation = Attenuation::default();
+		assert_gains(attenuation.gains(vec2(0.0, 50.0)), [1.0, 1.0]);
+		assert_gains(attenuation.gains(Vec2::ZERO), [1.0, 1.0]);
+	}
+
+	#[test]
+	fn panning_turns_the_far_side_down() {
+		let attenuation = Attenuation::default();
+		assert_gains(attenuation.gains(vec2(-100.0, 0.0)), [1.0, 0.0]);
+		assert_gains(attenuation.gains(vec2(100.0, 0.0)), [0.0, 1.0]);
+		let [left, right] = attenuation.gains(vec2(50.0, 0.0));
+		assert!(left < 1.0 && right == 1.0);
+	}
+
+	#[test]
+	fn volume_fades_linearly_to_the_max_distance() {
+		let attenuation = Attenuation {
+			reference_distance: 100.0,
+			max_distance: 300.0,
+		};
+		assert_gains(attenuation.gains(vec2(0.0, 100.0)), [1.0, 1.0]);
+		assert_gains(attenuation.gains(vec2(0.0, 200.0)), [0.5, 0.5]);
+		assert_gains(attenuation.gains(vec2(0.0, 300.0)), [0.0, 0.0]);
+		assert_gains(attenuation.gains(vec2(0.0, 1000.0)), [0.0, 0.0]);
+	}
+
+	#[test]
+	fn zero_reference_distance_pans_without_nan() {
+		let attenuation = Attenuation {
+			reference_distance: 0.0,
+			max_distance: 100.0,
+		};
+		assert_gains(attenuation.gains(Vec2::ZERO), [1.0, 1.0]);
+		assert_gains(attenuation.gains(vec2(-50.0, 0.0)), [0.5, 0.0]);
+	}
+
+	#[test]
+	fn invalid_attenuation_is_rejected() {
+		let mut audio = Audio::new(AudioOutput::Null);
+		for (reference_distance, max_distance) in [
+			(100.0, 100.0),
+			(200.0, 100.0),
+			(-1.0, 100.0),
+			(0.0, f32::NAN),
+		] {
+			let attenuation = Attenuation {
+				reference_distance,
+				max_distance,
+			};
+			assert!(matches!(
+				audio.set_attenuation(attenuation),
+				Err(AudioError::Attenuation(_))
+			));
+		}
+		assert!(audio.set_attenuation(Attenuation::default()).is_ok());
+	}
+
+	#[test]
+	fn empty_formats_have_no_duration() {
+		assert_eq!(
+			Sound::from_samples(0, 44100, vec![0.0; 4]).duration(),
+			std::time::Duration::ZERO
+		);
+		assert_eq!(
+			Sound::from_samples(2, 0, vec![0.0; 4]).duration(),
+			std::time::Duration::ZERO
+		);
+		assert_eq!(
+			Sound::from_samples(2, 4, vec![0.0; 16]).duration(),
+			std::time::Duration::from_secs(2)
+		);
+	}
+
+	#[test]
+	fn music_crossfades_on_the_null_backend() {
+		let mut audio = Audio::new(AudioOutput::Null);
+		let mut world = crate::ecs::World::default();
+		let second = std::time::Duration::from_secs(1);
+		audio.play_music(&silence(10), second).unwrap();
+		audio.update(&mut world, second);
+		assert_eq!(audio.music.as_ref().unwrap().gain, 1.0);
+
+		audio.play_music(&silence(10), 2 * second).unwrap();
+		audio.update(&mut world, second);
+		assert_eq!(audio.playing(), 2);
+		assert_eq!(audio.music.as_ref().unwrap().gain, 0.5);
+		assert_eq!(audio.voices[0].gain, 0.5);
+
+		audio.update(&mut world, second);
+		assert_eq!(audio.playing(), 1);
+		assert_eq!(audio.music.as_ref().unwrap().gain, 1.0);
+	}
+
+	#[test]
+	fn bus_and_master_volumes_scale_sinks() {
+		let mut audio = Audio::new(AudioOutput::Null);
+		audio.play(&silence(10), 0.5).unwrap();
+		audio
+			.play_music(&silence(10), std::time::Duration::ZERO)
+			.unwrap();
+		audio.set_master_volume(0.5);
+		audio.set_bus_volume(Bus::Sfx, 0.5);
+		assert_eq!(audio.voices[0].sink.volume(), 0.125);
+		assert_eq!(audio.music.as_ref().unwrap().sink.volume(), 0.5);
+	}
+
+	#[test]
+	fn one_shots_finish_on_the_null_backend() {
+		let mut audio = Audio::new(AudioOutput::Null);
+		let mut world = crate::ecs::World::default();
+		audio.play(&silence(2), 1.0).unwrap();
+		audio.update(&mut world, std::time::Duration::from_secs(1));
+		assert_eq!(audio.playing(), 1);
+		audio.update(&mut world, std::time::Duration::from_secs(2));
+		assert_eq!(audio.playing(), 0);
+	}
+}
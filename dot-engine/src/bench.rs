@@ -0,0 +1,72 @@
+//! Dispatch throughput benchmark, run with `dot --bench`.
+//!
+//! Renders a number of headless frames with each entry point in [`ENTRY_POINTS`] and prints the
+//! average wall time per frame, from submitting the frames until the GPU has finished them.
+//! `main_1x1` is the gradient with one invocation per workgroup, as a baseline for the workgroup
+//! size `main` is compiled with. Set `DOT_DEVICE` to pick the device, e.g. `DOT_DEVICE=llvmpipe`
+//! for lavapipe.
+
+/// Entry points to compare, the first being the baseline.
+pub const ENTRY_POINTS: &[crate::shaders::EntryPoint] = &[
+	crate::shaders::EntryPoint::Main1x1,
+	crate::shaders::EntryPoint::Main,
+];
+
+pub struct Options {
+	pub extent: [u32; 2],
+	pub frames: u32,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			extent: [1920, 1080],
+			frames: 100,
+		}
+	}
+}
+
+/// Runs the benchmark, printing a line per entry point.
+pub fn run(shaders: &crate::reflect::Modules, options: &Options) -> Result<(), String> {
+	let device_selection = crate::DeviceSelection::from_env().map_err(|e| e.to_string())?;
+	let mut baseline = None;
+	for &entry_point in ENTRY_POINTS {
+		let frame_time = measure(shaders, entry_point, options, &device_selection)
+			.map_err(|e| format!("{entry_point}: {}", crate::report(&e)))?;
+		let baseline = *baseline.get_or_insert(frame_time);
+		println!(
+			"bench {entry_point}: {:.3} ms/frame wall time ({:.2}x) at {}x{} over {} frames",
+			frame_time.as_secs_f64() * 1000.0,
+			baseline.as_secs_f64() / frame_time.as_secs_f64(),
+			options.extent[0],
+			options.extent[1],
+			options.frames,
+		);
+	}
+	Ok(())
+}
+
+/// Average wall time per frame of `entry_point`, after one warm-up frame.
+fn measure(
+	shaders: &crate::reflect::Modules,
+	entry_point: crate::shaders::EntryPoint,
+	options: &Options,
+	device_selection: &crate::DeviceSelection,
+) -> Result<std::time::Duration, crate::RendererError> {
+	let mut renderer = crate::Renderer::new_headless(
+		options.extent,
+		vulkano::Version::major_minor(0, 1),
+		shaders,
+		&crate::graph::RenderGraph::single(entry_point),
+		device_selection,
+	)?;
+	renderer.render_offscreen(None)?;
+	renderer.wait_idle()?;
+
+	let start = std::time::Instant::now();
+	for _ in 0..options.frames {
+		renderer.render_offscreen(None)?;
+	}
+	renderer.wait_idle()?;
+	Ok(start.elapsed() / options.frames.max(1))
+}
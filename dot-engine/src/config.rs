@@ -0,0 +1,877 @@
+//! Engine settings, read from a TOML file and the command line.
+//!
+//! [`Config::from_args`] starts from the defaults, applies `dot.toml` from the working directory
+//! (or the file given with `--config PATH`), then `DOT_DEVICE`, then the command line. Every key
+//! is optional; a file with all of them at their defaults reads:
+//!
+//! ```toml
+//! app_version = "0.1"
+//! device = "auto"         # as DOT_DEVICE: auto, an index, a device type or a name substring
+//! headless = false
+//! # frames = 100          # exit after this many frames; headless runs render one by default
+//! # shader = "path.spv"   # entry points from a SPIR-V file instead of the embedded modules
+//! # bindings = "path"     # action map file, see dot::input
+//! # screenshot = "path.png"
+//! # record = "path"
+//! # replay = "path"
+//!
+//! [window]
+//! title = "Dot"
+//! size = "800x600"
+//! # min_size = "320x240"
+//! resizable = true
+//! mode = "windowed"       # or borderless, exclusive
+//! monitor = "current"     # or primary, or an index
+//! cursor_grab = "none"    # or confined, locked
+//! cursor_visible = true
+//!
+//! [surface]
+//! present_mode = "fifo"   # or mailbox, immediate
+//! format = "auto"        # or a format such as B8G8R8A8_UNORM, tried first
+//! color_space = "srgb"    # or hdr10, scrgb, falling back to srgb
+//! composite_alpha = "opaque"  # or pre_multiplied, post_multiplied, inherit
+//!
+//! [game_loop]
+//! tick_rate = 60.0
+//! max_ticks_per_frame = 5
+//! max_frame_time = 0.25   # seconds
+//! frame_rate_limit = 0.0  # frames per second, 0 for unlimited
+//!
+//! [audio]
+//! output = "device"       # or null
+//! ```
+//!
+//! Unknown keys and values of the wrong type or out of range are errors naming the key, so typos
+//! don't go unnoticed.
+
+use crate::{audio, game_loop, input, reflect, window, DeviceSelection, LoopConfig};
+
+/// The file [`Config::from_args`] reads if it exists and no `--config` is given.
+pub const DEFAULT_PATH: &str = "dot.toml";
+
+/// How [`crate::run`] runs an app.
+pub struct Config {
+	/// Passed to the Vulkan driver, which may use it to apply app-specific workarounds.
+	pub app_version: vulkano::Version,
+	/// Its size is also that of the offscreen image rendered with [`Config::headless`].
+	pub window: window::WindowConfig,
+	pub surface: window::SurfaceConfig,
+	/// Modules the render graph's entry points are looked up in.
+	pub shaders: reflect::Modules,
+	pub device_selection: DeviceSelection,
+	pub game_loop: LoopConfig,
+	/// Headless runs always use [`audio::AudioOutput::Null`].
+	pub audio: audio::AudioOutput,
+	/// The initial bindings of [`input::Input::actions`].
+	pub actions: input::ActionMap,
+	/// Render offscreen instead of opening a window.
+	pub headless: bool,
+	/// Exit after rendering this many frames. Headless runs default to one, replays render the
+	/// recorded frames.
+	pub frames: Option<u64>,
+	/// Save the first frame to this PNG file, or the last one of a replay.
+	pub screenshot: Option<std::path::PathBuf>,
+	/// Record the input to this file, see [`crate::replay`].
+	pub record: Option<std::path::PathBuf>,
+	/// Replay the input recorded in this file without a window instead of running interactively.
+	pub replay: Option<std::path::PathBuf>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			app_version: vulkano::Version::major_minor(0, 1),
+			window: window::WindowConfig::default(),
+			surface: window::SurfaceConfig::default(),
+			shaders: crate::shaders::modules(),
+			device_selection: DeviceSelection::default(),
+			game_loop: LoopConfig::default(),
+			audio: audio::AudioOutput::default(),
+			actions: input::ActionMap::default(),
+			headless: false,
+			frames: None,
+			screenshot: None,
+			record: None,
+			replay: None,
+		}
+	}
+}
+
+impl Config {
+	/// The defaults with the file at `path` applied.
+	pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
+		let mut config = Self::default();
+		config.apply_file(path.as_ref())?;
+		Ok(config)
+	}
+
+	/// The defaults with the config file, `DOT_DEVICE` and the command line applied, see the
+	/// [module docs](self). Besides the keys of the file, the command line takes `--device`,
+	/// `--headless`, `--size WIDTHxHEIGHT`, `--frames N`, `--shader PATH`, `--bindings PATH`,
+	/// `--screenshot PATH`, `--record PATH` and `--replay PATH`. Any other argument is an error.
+	pub fn from_args() -> Result<Self, String> {
+		Self::from_args_with(&[], &[]).map(|(config, _)| config)
+	}
+
+	/// Like [`Self::from_args`], also accepting the app's own `switches`, such as `--verbose`,
+	/// and `options` taking a value. Returns the parsed command line too, for the app to read
+	/// them from.
+	pub fn from_args_with(switches: &[&str], options: &[&str]) -> Result<(Self, Args), String> {
+		let args: Vec<String> = std::env::args().skip(1).collect();
+		let args = Args::parse(&args, switches, options).map_err(|e| crate::report(&e))?;
+		let mut config = Self::default();
+		let applied = match args.value("--config") {
+			Some(path) => config.apply_file(path.as_ref()),
+			None if std::path::Path::new(DEFAULT_PATH).exists() => {
+				config.apply_file(DEFAULT_PATH.as_ref())
+			},
+			None => Ok(()),
+		};
+		applied.map_err(|e| crate::report(&e))?;
+		if let Ok(device) = std::env::var(DeviceSelection::ENV_VAR) {
+			config.device_selection =
+				parse_device(DeviceSelection::ENV_VAR, &device).map_err(|e| crate::report(&e))?;
+		}
+		config.apply_args(&args).map_err(|e| crate::report(&e))?;
+		Ok((config, args))
+	}
+
+	fn apply_file(&mut self, path: &std::path::Path) -> Result<(), ConfigError> {
+		let source = std::fs::read_to_string(path).map_err(|e| ConfigError::Io {
+			path: path.to_owned(),
+			source: e,
+		})?;
+		self.apply_toml(&source).map_err(|e| ConfigError::File {
+			path: path.to_owned(),
+			source: Box::new(e),
+		})
+	}
+
+	/// Applies the keys set in `source`, the contents of a config file.
+	pub fn apply_toml(&mut self, source: &str) -> Result<(), ConfigError> {
+		let mut root = Table::new("", source.parse()?);
+		if let Some(version) = root.string("app_version")? {
+			self.app_version =
+				parse_version(&version).ok_or_else(|| ConfigError::InvalidValue {
+					key: "app_version".to_owned(),
+					expected: "a version such as \"1.2\" or \"1.2.3\"".to_owned(),
+				})?;
+		}
+		match root.take("device") {
+			Some((_, toml::Value::Integer(index))) if index >= 0 => {
+				self.device_selection = DeviceSelection::Index(index as usize)
+			},
+			Some((key, toml::Value::String(device))) => {
+				self.device_selection = parse_device(&key, &device)?
+			},
+			Some((key, _)) => return Err(invalid_device(&key)),
+			None => (),
+		}
+		if let Some(headless) = root.bool("headless")? {
+			self.headless = headless;
+		}
+		if let Some(frames) = root.integer("frames", 1)? {
+			self.frames = Some(frames);
+		}
+		if let Some(path) = root.string("shader")? {
+			self.shaders = load_shader(&path)?;
+		}
+		if let Some(path) = root.string("bindings")? {
+			self.actions = load_bindings(&path)?;
+		}
+		if let Some(path) = root.string("screenshot")? {
+			self.screenshot = Some(path.into());
+		}
+		if let Some(path) = root.string("record")? {
+			self.record = Some(path.into());
+		}
+		if let Some(path) = root.string("replay")? {
+			self.replay = Some(path.into());
+		}
+
+		if let Some(mut table) = root.table("window")? {
+			let window_config = &mut self.window;
+			if let Some(title) = table.string("title")? {
+				window_config.title = title;
+			}
+			if let Some(size) = table.size("size")? {
+				window_config.size = size;
+			}
+			if let Some(min_size) = table.size("min_size")? {
+				window_config.min_size = Some(min_size);
+			}
+			if let Some(resizable) = table.bool("resizable")? {
+				window_config.resizable = resizable;
+			}
+			if let Some(mode) = table.choice("mode", WINDOW_MODES)? {
+				window_config.mode = mode;
+			}
+			match table.take("monitor") {
+				Some((_, toml::Value::Integer(index))) if index >= 0 => {
+					window_config.monitor = window::MonitorSelection::Index(index as usize)
+				},
+				Some((_, toml::Value::String(monitor))) if monitor == "current" => {
+					window_config.monitor = window::MonitorSelection::Current
+				},
+				Some((_, toml::Value::String(monitor))) if monitor == "primary" => {
+					window_config.monitor = window::MonitorSelection::Primary
+				},
+				Some((key, _)) => {
+					return Err(ConfigError::InvalidValue {
+						key,
+						expected: "\"current\", \"primary\" or a monitor index".to_owned(),
+					})
+				},
+				None => (),
+			}
+			if let Some(cursor_grab) = table.choice("cursor_grab", CURSOR_GRABS)? {
+				window_config.cursor_grab = cursor_grab;
+			}
+			if let Some(cursor_visible) = table.bool("cursor_visible")? {
+				window_config.cursor_visible = cursor_visible;
+			}
+			table.finish()?;
+		}
+
+		if let Some(mut table) = root.table("surface")? {
+			if let Some(present_mode) = table.choice("present_mode", PRESENT_MODES)? {
+				self.surface.present_mode = present_mode;
+			}
+			if let Some(format) = table.choice("format", SURFACE_FORMATS)? {
+				self.surface.format = format;
+			}
+			if let Some(color_space) = table.choice("color_space", COLOR_SPACES)? {
+				self.surface.color_space = color_space;
+			}
+			if let Some(composite_alpha) = table.choice("composite_alpha", COMPOSITE_ALPHAS)? {
+				self.surface.composite_alpha = composite_alpha;
+			}
+			table.finish()?;
+		}
+
+		if let Some(mut table) = root.table("game_loop")? {
+			if let Some(tick_rate) = table.positive_float("tick_rate")? {
+				self.game_loop.tick_rate = tick_rate;
+			}
+			if let Some(max_ticks) = table.integer("max_ticks_per_frame", 1)? {
+				self.game_loop.max_ticks_per_frame =
+					max_ticks
+						.try_into()
+						.map_err(|_| ConfigError::InvalidValue {
+							key: "game_loop.max_ticks_per_frame".to_owned(),
+							expected: "an integer that fits in 32 bits".to_owned(),
+						})?;
+			}
+			if let Some(max_frame_time) = table.positive_float("max_frame_time")? {
+				self.game_loop.max_frame_time = std::time::Duration::from_secs_f64(max_frame_time);
+			}
+			if let Some((key, value)) = table.take("frame_rate_limit") {
+				let limit = value.as_float().or(value.as_integer().map(|i| i as f64));
+				self.game_loop.pacing = match limit {
+					Some(limit) if limit == 0.0 => game_loop::FramePacing::Unlimited,
+					Some(limit) if limit > 0.0 => game_loop::FramePacing::Limit(limit),
+					_ => {
+						return Err(ConfigError::InvalidValue {
+							key,
+							expected: "a frame rate, or 0 for unlimited".to_owned(),
+						})
+					},
+				};
+			}
+			table.finish()?;
+		}
+
+		if let Some(mut table) = root.table("audio")? {
+			if let Some(output) = table.choice("output", AUDIO_OUTPUTS)? {
+				self.audio = output;
+			}
+			table.finish()?;
+		}
+
+		root.finish()
+	}
+
+	/// Applies the overrides given on the command line.
+	fn apply_args(&mut self, args: &Args) -> Result<(), ConfigError> {
+		if let Some(device) = args.value("--device") {
+			self.device_selection = parse_device("--device", device)?;
+		}
+		if args.has("--headless") {
+			self.headless = true;
+		}
+		if let Some(size) = args.value("--size") {
+			self.window.size = parse_size(size).ok_or_else(|| ConfigError::InvalidValue {
+				key: "--size".to_owned(),
+				expected: "a size such as 1280x720".to_owned(),
+			})?;
+		}
+		if let Some(frames) = args.value("--frames") {
+			self.frames = Some(
+				frames
+					.parse()
+					.ok()
+					.filter(|&frames| frames > 0)
+					.ok_or_else(|| ConfigError::InvalidValue {
+						key: "--frames".to_owned(),
+						expected: "a positive number of frames".to_owned(),
+					})?,
+			);
+		}
+		if let Some(path) = args.value("--shader") {
+			self.shaders = load_shader(path)?;
+		}
+		if let Some(path) = args.value("--bindings") {
+			self.actions = load_bindings(path)?;
+		}
+		if let Some(path) = args.value("--screenshot") {
+			self.screenshot = Some(path.into());
+		}
+		if let Some(path) = args.value("--record") {
+			self.record = Some(path.into());
+		}
+		if let Some(path) = args.value("--replay") {
+			self.replay = Some(path.into());
+		}
+		Ok(())
+	}
+}
+
+/// Command line switches [`Config::from_args`] reads.
+const SWITCHES: &[&str] = &["--headless"];
+
+/// Command line options [`Config::from_args`] reads, which take a value.
+const OPTIONS: &[&str] = &[
+	"--config",
+	"--device",
+	"--size",
+	"--frames",
+	"--shader",
+	"--bindings",
+	"--screenshot",
+	"--record",
+	"--replay",
+];
+
+/// The command line split into switches and options with their values.
+pub struct Args {
+	/// In order, `None` for switches.
+	args: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+	/// Splits `args`, failing on arguments that are neither the engine's nor in the app's
+	/// `switches` and `options`, and on options without a value.
+	fn parse(args: &[String], switches: &[&str], options: &[&str]) -> Result<Self, ConfigError> {
+		let mut parsed = Vec::new();
+		let mut args = args.iter();
+		while let Some(arg) = args.next() {
+			if SWITCHES.contains(&arg.as_str()) || switches.contains(&arg.as_str()) {
+				parsed.push((arg.clone(), None));
+			} else if OPTIONS.contains(&arg.as_str()) || options.contains(&arg.as_str()) {
+				match args.next() {
+					Some(value) if !value.starts_with("--") => {
+						parsed.push((arg.clone(), Some(value.clone())))
+					},
+					_ => return Err(ConfigError::MissingValue(arg.clone())),
+				}
+			} else {
+				return Err(ConfigError::UnknownArgument(arg.clone()));
+			}
+		}
+		Ok(Self { args: parsed })
+	}
+
+	/// Whether the switch or option was given.
+	pub fn has(&self, switch: &str) -> bool {
+		self.args.iter().any(|(arg, _)| arg == switch)
+	}
+
+	/// The value of the last occurrence of `option`.
+	pub fn value(&self, option: &str) -> Option<&str> {
+		self.args
+			.iter()
+			.rev()
+			.find(|(arg, _)| arg == option)
+			.and_then(|(_, value)| value.as_deref())
+	}
+}
+
+const WINDOW_MODES: &[(&str, window::WindowMode)] = &[
+	("windowed", window::WindowMode::Windowed),
+	("borderless", window::WindowMode::BorderlessFullscreen),
+	("exclusive", window::WindowMode::ExclusiveFullscreen),
+];
+
+const CURSOR_GRABS: &[(&str, window::CursorGrab)] = &[
+	("none", window::CursorGrab::None),
+	("confined", window::CursorGrab::Confined),
+	("locked", window::CursorGrab::Locked),
+];
+
+const PRESENT_MODES: &[(&str, window::PresentMode)] = &[
+	("fifo", window::PresentMode::Fifo),
+	("mailbox", window::PresentMode::Mailbox),
+	("immediate", window::PresentMode::Immediate),
+];
+
+const SURFACE_FORMATS: &[(&str, Option<vulkano::format::Format>)] = &[
+	("auto", None),
+	(
+		"B8G8R8A8_UNORM",
+		Some(vulkano::format::Format::B8G8R8A8_UNORM),
+	),
+	(
+		"B8G8R8A8_SRGB",
+		Some(vulkano::format::Format::B8G8R8A8_SRGB),
+	),
+	(
+		"R8G8B8A8_UNORM",
+		Some(vulkano::format::Format::R8G8B8A8_UNORM),
+	),
+	(
+		"R8G8B8A8_SRGB",
+		Some(vulkano::format::Format::R8G8B8A8_SRGB),
+	),
+	(
+		"A2B10G10R10_UNORM_PACK32",
+		Some(vulkano::format::Format::A2B10G10R10_UNORM_PACK32),
+	),
+	(
+		"A2R10G10B10_UNORM_PACK32",
+		Some(vulkano::format::Format::A2R10G10B10_UNORM_PACK32),
+	),
+	(
+		"R16G16B16A16_SFLOAT",
+		Some(vulkano::format::Format::R16G16B16A16_SFLOAT),
+	),
+];
+
+const COLOR_SPACES: &[(&str, window::ColorSpace)] = &[
+	("srgb", window::ColorSpace::Srgb),
+	("hdr10", window::ColorSpace::Hdr10),
+	("scrgb", window::ColorSpace::ScRgb),
+];
+
+const COMPOSITE_ALPHAS: &[(&str, vulkano::swapchain::CompositeAlpha)] = &[
+	("opaque", vulkano::swapchain::CompositeAlpha::Opaque),
+	(
+		"pre_multiplied",
+		vulkano::swapchain::CompositeAlpha::PreMultiplied,
+	),
+	(
+		"post_multiplied",
+		vulkano::swapchain::CompositeAlpha::PostMultiplied,
+	),
+	("inherit", vulkano::swapchain::CompositeAlpha::Inherit),
+];
+
+const AUDIO_OUTPUTS: &[(&str, audio::AudioOutput)] = &[
+	("device", audio::AudioOutput::Device),
+	("null", audio::AudioOutput::Null),
+];
+
+#[derive(Debug)]
+pub enum ConfigError {
+	Io {
+		path: std::path::PathBuf,
+		source: std::io::Error,
+	},
+	/// An error in the config file at `path`.
+	File {
+		path: std::path::PathBuf,
+		source: Box<ConfigError>,
+	},
+	/// The file isn't valid TOML.
+	Parse(toml::de::Error),
+	UnknownKey(String),
+	/// A command line argument that is neither the engine's nor the app's.
+	UnknownArgument(String),
+	/// A command line option is last or followed by another option instead of its value.
+	MissingValue(String),
+	/// The key, command line option or environment variable has a value of the wrong type or out
+	/// of range.
+	InvalidValue {
+		key: String,
+		expected: String,
+	},
+	/// Loading the file a key or option points to failed.
+	Load {
+		path: String,
+		source: Box<dyn std::error::Error>,
+	},
+}
+
+impl std::fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io { path, .. } => write!(f, "failed to read config {}", path.display()),
+			Self::File { path, .. } => write!(f, "invalid config {}", path.display()),
+			Self::Parse(_) => write!(f, "failed to parse config"),
+			Self::UnknownKey(key) => write!(f, "unknown key {key:?}"),
+			Self::UnknownArgument(arg) => write!(f, "unknown argument {arg:?}"),
+			Self::MissingValue(option) => write!(f, "missing value for {option:?}"),
+			Self::InvalidValue { key, expected } => {
+				write!(f, "invalid value for {key:?}, expected {expected}")
+			},
+			Self::Load { path, .. } => write!(f, "failed to load {path}"),
+		}
+	}
+}
+
+impl std::error::Error for ConfigError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io { source, .. } => Some(source),
+			Self::File { source, .. } => Some(source.as_ref()),
+			Self::Parse(e) => Some(e),
+			Self::UnknownKey(_)
+			| Self::UnknownArgument(_)
+			| Self::MissingValue(_)
+			| Self::InvalidValue { .. } => None,
+			Self::Load { source, .. } => Some(source.as_ref()),
+		}
+	}
+}
+
+impl From<toml::de::Error> for ConfigError {
+	fn from(e: toml::de::Error) -> Self {
+		Self::Parse(e)
+	}
+}
+
+/// The keys of a TOML table, taken out as they are applied so that any left over are unknown.
+struct Table {
+	/// Prefixed to keys in errors, e.g. `window.`.
+	prefix: String,
+	table: toml::Table,
+}
+
+impl Table {
+	fn new(prefix: &str, table: toml::Table) -> Self {
+		Self {
+			prefix: prefix.to_owned(),
+			table,
+		}
+	}
+
+	/// Removes `key`, returning its full name and its value.
+	fn take(&mut self, key: &str) -> Option<(String, toml::Value)> {
+		let value = self.table.remove(key)?;
+		Some((format!("{}{key}", self.prefix), value))
+	}
+
+	fn string(&mut self, key: &str) -> Result<Option<String>, ConfigError> {
+		self.take_as(key, "a string", |value| match value {
+			toml::Value::String(string) => Some(string),
+			_ => None,
+		})
+	}
+
+	fn bool(&mut self, key: &str) -> Result<Option<bool>, ConfigError> {
+		self.take_as(key, "true or false", |value| value.as_bool())
+	}
+
+	fn integer(&mut self, key: &str, minimum: u64) -> Result<Option<u64>, ConfigError> {
+		self.take_as(key, "a positive integer", |value| {
+			value
+				.as_integer()
+				.and_then(|i| u64::try_from(i).ok())
+				.filter(|&i| i >= minimum)
+		})
+	}
+
+	/// A float greater than 0, also accepting integers.
+	fn positive_float(&mut self, key: &str) -> Result<Option<f64>, ConfigError> {
+		self.take_as(key, "a positive number", |value| {
+			value
+				.as_float()
+				.or(value.as_integer().map(|i| i as f64))
+				.filter(|&value| value > 0.0)
+		})
+	}
+
+	/// A size written `WIDTHxHEIGHT`.
+	fn size(&mut self, key: &str) -> Result<Option<[u32; 2]>, ConfigError> {
+		self.take_as(key, "a size such as \"1280x720\"", |value| {
+			parse_size(value.as_str()?)
+		})
+	}
+
+	/// One of the names in `choices`, the value of the first one matching.
+	fn choice<T: Clone>(
+		&mut self,
+		key: &str,
+		choices: &[(&str, T)],
+	) -> Result<Option<T>, ConfigError> {
+		let Some((key, value)) = self.take(key) else {
+			return Ok(None);
+		};
+		let choice = value.as_str().and_then(|value| {
+			choices
+				.iter()
+				.find(|(name, _)| name.eq_ignore_ascii_case(value))
+		});
+		match choice {
+			Some((_, choice)) => Ok(Some(choice.clone())),
+			None => Err(ConfigError::InvalidValue {
+				key,
+				expected: format!(
+					"one of {}",
+					choices
+						.iter()
+						.map(|(name, _)| format!("{name:?}"))
+						.collect::<Vec<_>>()
+						.join(", ")
+				),
+			}),
+		}
+	}
+
+	fn table(&mut self, key: &str) -> Result<Option<Self>, ConfigError> {
+		let prefix = format!("{}{key}.", self.prefix);
+		self.take_as(key, "a table", |value| match value {
+			toml::Value::Table(table) => Some(Self::new(&prefix, table)),
+			_ => None,
+		})
+	}
+
+	fn take_as<T>(
+		&mut self,
+		key: &str,
+		expected: &'static str,
+		convert: impl FnOnce(toml::Value) -> Option<T>,
+	) -> Result<Option<T>, ConfigError> {
+		let Some((key, value)) = self.take(key) else {
+			return Ok(None);
+		};
+		convert(value)
+			.map(Some)
+			.ok_or_else(|| ConfigError::InvalidValue {
+				key,
+				expected: expected.to_owned(),
+			})
+	}
+
+	/// Fails on the first key that wasn't taken.
+	fn finish(self) -> Result<(), ConfigError> {
+		match self.table.keys().next() {
+			Some(key) => Err(ConfigError::UnknownKey(format!("{}{key}", self.prefix))),
+			None => Ok(()),
+		}
+	}
+}
+
+fn parse_size(size: &str) -> Option<[u32; 2]> {
+	let (width, height) = size.split_once('x')?;
+	let size = [width.trim().parse().ok()?, height.trim().parse().ok()?];
+	(!size.contains(&0)).then_some(size)
+}
+
+fn parse_version(version: &str) -> Option<vulkano::Version> {
+	let mut parts = version.split('.').map(str::parse);
+	let major = parts.next()?.ok()?;
+	let minor = parts.next()?.ok()?;
+	let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+	if parts.next().is_some() {
+		return None;
+	}
+	Some(vulkano::Version {
+		major,
+		minor,
+		patch,
+	})
+}
+
+fn parse_device(key: &str, device: &str) -> Result<DeviceSelection, ConfigError> {
+	DeviceSelection::parse(device).ok_or_else(|| invalid_device(key))
+}
+
+fn invalid_device(key: &str) -> ConfigError {
+	ConfigError::InvalidValue {
+		key: key.to_owned(),
+		expected: "\"auto\", a device index, a device type or a name substring".to_owned(),
+	}
+}
+
+fn load_shader(path: &str) -> Result<reflect::Modules, ConfigError> {
+	let spirv = reflect::load(path).map_err(|e| ConfigError::Load {
+		path: path.to_owned(),
+		source: Box::new(e),
+	})?;
+	Ok(reflect::Modules::single(spirv))
+}
+
+fn load_bindings(path: &str) -> Result<input::ActionMap, ConfigError> {
+	input::ActionMap::load(path).map_err(|e| ConfigError::Load {
+		path: path.to_owned(),
+		source: Box::new(e),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(args: &[&str]) -> Result<Args, ConfigError> {
+		let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+		Args::parse(&args, &["--verbose"], &["--level"])
+	}
+
+	fn apply_args(config: &mut Config, list: &[&str]) -> Result<(), ConfigError> {
+		config.apply_args(&args(list)?)
+	}
+
+	fn invalid_key(result: Result<(), ConfigError>) -> String {
+		match result {
+			Err(ConfigError::InvalidValue { key, .. }) => key,
+			result => panic!("expected an invalid value, got {result:?}"),
+		}
+	}
+
+	#[test]
+	fn applies_file_keys() {
+		let mut config = Config::default();
+		config
+			.apply_toml(
+				r#"
+				device = 1
+				frames = 3
+				[window]
+				size = "1280x720"
+				mode = "Borderless"
+				monitor = 2
+				[game_loop]
+				tick_rate = 120
+				frame_rate_limit = 0
+				"#,
+			)
+			.unwrap();
+		assert_eq!(config.device_selection, DeviceSelection::Index(1));
+		assert_eq!(config.frames, Some(3));
+		assert_eq!(config.window.size, [1280, 720]);
+		assert_eq!(config.window.mode, window::WindowMode::BorderlessFullscreen);
+		assert_eq!(config.window.monitor, window::MonitorSelection::Index(2));
+		assert_eq!(config.game_loop.tick_rate, 120.0);
+		assert_eq!(config.game_loop.pacing, game_loop::FramePacing::Unlimited);
+	}
+
+	#[test]
+	fn names_the_bad_key() {
+		let mut config = Config::default();
+		assert_eq!(
+			invalid_key(config.apply_toml("[window]\nsize = \"1280 by 720\"")),
+			"window.size"
+		);
+		assert_eq!(
+			invalid_key(config.apply_toml("[game_loop]\ntick_rate = -1")),
+			"game_loop.tick_rate"
+		);
+		assert_eq!(
+			invalid_key(config.apply_toml("[surface]\npresent_mode = \"vsync\"")),
+			"surface.present_mode"
+		);
+		assert_eq!(invalid_key(config.apply_toml("device = \"-1\"")), "device");
+		assert_eq!(invalid_key(config.apply_toml("device = true")), "device");
+		assert_eq!(invalid_key(config.apply_toml("frames = 0")), "frames");
+	}
+
+	#[test]
+	fn rejects_unknown_keys() {
+		let mut config = Config::default();
+		assert!(matches!(
+			config.apply_toml("headles = true"),
+			Err(ConfigError::UnknownKey(key)) if key == "headles"
+		));
+		assert!(matches!(
+			config.apply_toml("[window]\ntitle = \"Dot\"\nfulscreen = true"),
+			Err(ConfigError::UnknownKey(key)) if key == "window.fulscreen"
+		));
+		assert!(matches!(
+			config.apply_toml("[graphics]"),
+			Err(ConfigError::UnknownKey(key)) if key == "graphics"
+		));
+		assert!(matches!(
+			config.apply_toml("[window"),
+			Err(ConfigError::Parse(_))
+		));
+	}
+
+	#[test]
+	fn applies_arguments() {
+		let mut config = Config::default();
+		apply_args(
+			&mut config,
+			&[
+				"--verbose",
+				"--size",
+				"640x480",
+				"--level",
+				"3",
+				"--device",
+				"llvmpipe",
+				"--headless",
+				"--frames",
+				"2",
+				"--frames",
+				"5",
+			],
+		)
+		.unwrap();
+		assert_eq!(config.window.size, [640, 480]);
+		assert_eq!(
+			config.device_selection,
+			DeviceSelection::Name("llvmpipe".to_owned())
+		);
+		assert!(config.headless);
+		assert_eq!(config.frames, Some(5));
+	}
+
+	#[test]
+	fn keeps_app_arguments() {
+		// A switch isn't taken as an option's value.
+		assert!(matches!(
+			args(&["--level", "--verbose"]),
+			Err(ConfigError::MissingValue(option)) if option == "--level"
+		));
+
+		let args = args(&["--headless", "--level", "3", "--verbose", "--level", "4"]).unwrap();
+		assert!(args.has("--verbose"));
+		assert!(args.has("--headless"));
+		assert_eq!(args.value("--level"), Some("4"));
+		assert!(!args.has("--size"));
+		assert_eq!(args.value("--size"), None);
+	}
+
+	#[test]
+	fn rejects_bad_arguments() {
+		let mut config = Config::default();
+		assert!(matches!(
+			args(&["--headless", "--frames"]),
+			Err(ConfigError::MissingValue(option)) if option == "--frames"
+		));
+		assert!(matches!(
+			args(&["--size", "--headless"]),
+			Err(ConfigError::MissingValue(option)) if option == "--size"
+		));
+		assert!(matches!(
+			args(&["--headles"]),
+			Err(ConfigError::UnknownArgument(arg)) if arg == "--headles"
+		));
+		assert!(matches!(
+			args(&["--frames", "3", "extra"]),
+			Err(ConfigError::UnknownArgument(arg)) if arg == "extra"
+		));
+		assert_eq!(
+			invalid_key(apply_args(&mut config, &["--device", "-1"])),
+			"--device"
+		);
+		assert_eq!(
+			invalid_key(apply_args(&mut config, &["--frames", "0"])),
+			"--frames"
+		);
+		assert_eq!(
+			invalid_key(apply_args(&mut config, &["--size", "big"])),
+			"--size"
+		);
+	}
+}
@@ -0,0 +1,367 @@
+use crate::RendererError;
+
+/// How the renderer picks among the physical devices that meet its requirements.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceSelection {
+	/// Prefer discrete GPUs, then integrated, virtual and CPU devices.
+	#[default]
+	Auto,
+	/// The first suitable device whose name contains this, ignoring case.
+	Name(String),
+	/// The device at this position in enumeration order, as printed by `--list-devices`.
+	Index(usize),
+	/// The first suitable device of this type.
+	Type(vulkano::device::physical::PhysicalDeviceType),
+}
+
+impl DeviceSelection {
+	/// Name of the environment variable read by [`Self::from_env`].
+	pub const ENV_VAR: &'static str = "DOT_DEVICE";
+
+	/// Reads the policy from `DOT_DEVICE`, or [`Self::Auto`] if it is unset.
+	pub fn from_env() -> Result<Self, InvalidDeviceSelection> {
+		match std::env::var(Self::ENV_VAR) {
+			Ok(value) => Self::parse(&value).ok_or(InvalidDeviceSelection(value)),
+			Err(std::env::VarError::NotPresent) => Ok(Self::Auto),
+			Err(std::env::VarError::NotUnicode(value)) => {
+				Err(InvalidDeviceSelection(value.to_string_lossy().into_owned()))
+			},
+		}
+	}
+
+	/// Parses `auto`, a device index, a device type (`discrete`, `integrated`, `virtual`, `cpu`
+	/// or `other`) or otherwise a name substring such as `llvmpipe`. Returns `None` for numbers
+	/// that aren't an index, such as `-1`.
+	pub fn parse(value: &str) -> Option<Self> {
+		use vulkano::device::physical::PhysicalDeviceType;
+
+		let value = value.trim();
+		if let Ok(number) = value.parse::<i128>() {
+			return usize::try_from(number).ok().map(Self::Index);
+		}
+		Some(match value.to_ascii_lowercase().as_str() {
+			"" | "auto" => Self::Auto,
+			"discrete" => Self::Type(PhysicalDeviceType::DiscreteGpu),
+			"integrated" => Self::Type(PhysicalDeviceType::IntegratedGpu),
+			"virtual" => Self::Type(PhysicalDeviceType::VirtualGpu),
+			"cpu" => Self::Type(PhysicalDeviceType::Cpu),
+			"other" => Self::Type(PhysicalDeviceType::Other),
+			_ => Self::Name(value.to_owned()),
+		})
+	}
+}
+
+/// A `DOT_DEVICE` value that [`DeviceSelection::parse`] rejects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDeviceSelection(pub String);
+
+impl std::fmt::Display for InvalidDeviceSelection {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"invalid {} {:?}, expected \"auto\", a device index, a device type or a name substring",
+			DeviceSelection::ENV_VAR,
+			self.0,
+		)
+	}
+}
+
+impl std::error::Error for InvalidDeviceSelection {}
+
+/// Why a physical device was or wasn't chosen.
+#[derive(Clone, Debug)]
+pub enum DeviceStatus {
+	Selected,
+	/// The device is suitable but the selection policy preferred another one.
+	NotSelected,
+	MissingExtensions(Box<vulkano::device::DeviceExtensions>),
+	NoComputeQueue,
+	/// No queue family can present to the window surface.
+	NoPresentQueue,
+}
+
+impl std::fmt::Display for DeviceStatus {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Selected => write!(f, "selected"),
+			Self::NotSelected => write!(f, "suitable, not selected"),
+			Self::MissingExtensions(extensions) => write!(f, "missing extensions {extensions:?}"),
+			Self::NoComputeQueue => write!(f, "no compute queue"),
+			Self::NoPresentQueue => write!(f, "no queue can present to the window"),
+		}
+	}
+}
+
+/// A physical device as seen by the selection, for `--list-devices` and error messages.
+#[derive(Clone, Debug)]
+pub struct DeviceReport {
+	pub index: usize,
+	pub name: String,
+	pub device_type: vulkano::device::physical::PhysicalDeviceType,
+	pub api_version: vulkano::Version,
+	pub driver_name: Option<String>,
+	pub status: DeviceStatus,
+}
+
+impl std::fmt::Display for DeviceReport {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}: {} ({:?}, Vulkan {}",
+			self.index, self.name, self.device_type, self.api_version,
+		)?;
+		if let Some(driver_name) = &self.driver_name {
+			write!(f, ", driver {driver_name}")?;
+		}
+		write!(f, "): {}", self.status)
+	}
+}
+
+/// Queue families the renderer submits to. Compute work goes to a dedicated (async) compute
+/// family when the device has one; presenting uses the compute family if it can present and any
+/// other family that can otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct QueueFamilies {
+	pub compute: u32,
+	pub present: u32,
+}
+
+struct Candidate {
+	physical_device: std::sync::Arc<vulkano::device::physical::PhysicalDevice>,
+	queue_families: Option<QueueFamilies>,
+	report: DeviceReport,
+}
+
+/// Picks a physical device supporting `device_extensions` with a compute queue family and, if
+/// `surface` is given, a family that can present to it, according to `selection`.
+pub fn select_device(
+	instance: &std::sync::Arc<vulkano::instance::Instance>,
+	device_extensions: vulkano::device::DeviceExtensions,
+	surface: Option<&vulkano::swapchain::Surface>,
+	selection: &DeviceSelection,
+) -> Result<
+	(
+		std::sync::Arc<vulkano::device::physical::PhysicalDevice>,
+		QueueFamilies,
+	),
+	RendererError,
+> {
+	let candidates = evaluate(instance, device_extensions, surface, selection)?;
+	let selected = candidates
+		.iter()
+		.find(|candidate| matches!(candidate.report.status, DeviceStatus::Selected));
+	match selected {
+		Some(candidate) => Ok((
+			candidate.physical_device.clone(),
+			candidate.queue_families.unwrap(),
+		)),
+		None => Err(RendererError::NoSuitableDevice(
+			candidates
+				.into_iter()
+				.map(|candidate| candidate.report)
+				.collect(),
+		)),
+	}
+}
+
+/// Lists every physical device with the outcome of [`select_device`] for it.
+pub fn report_devices(
+	instance: &std::sync::Arc<vulkano::instance::Instance>,
+	device_extensions: vulkano::device::DeviceExtensions,
+	surface: Option<&vulkano::swapchain::Surface>,
+	selection: &DeviceSelection,
+) -> Result<Vec<DeviceReport>, RendererError> {
+	Ok(evaluate(instance, device_extensions, surface, selection)?
+		.into_iter()
+		.map(|candidate| candidate.report)
+		.collect())
+}
+
+/// The compute and present families among families with `queue_flags`, as described on
+/// [`QueueFamilies`]. `can_present` tells whether a family can present to the surface; without
+/// one, the compute family is used.
+fn choose_queue_families(
+	queue_flags: &[vulkano::device::QueueFlags],
+	can_present: Option<impl Fn(u32) -> bool>,
+) -> (Option<u32>, Option<u32>) {
+	use vulkano::device::QueueFlags;
+
+	let family_count = queue_flags.len() as u32;
+	let has_flags = |i: u32, flags| queue_flags[i as usize].intersects(flags);
+	let compute_family = (0..family_count)
+		.find(|&i| has_flags(i, QueueFlags::COMPUTE) && !has_flags(i, QueueFlags::GRAPHICS))
+		.or_else(|| (0..family_count).find(|&i| has_flags(i, QueueFlags::COMPUTE)));
+	let present_family = match can_present {
+		None => compute_family,
+		Some(can_present) => compute_family
+			.filter(|&i| can_present(i))
+			.or_else(|| (0..family_count).find(|&i| can_present(i))),
+	};
+	(compute_family, present_family)
+}
+
+fn evaluate(
+	instance: &std::sync::Arc<vulkano::instance::Instance>,
+	device_extensions: vulkano::device::DeviceExtensions,
+	surface: Option<&vulkano::swapchain::Surface>,
+	selection: &DeviceSelection,
+) -> Result<Vec<Candidate>, RendererError> {
+	let mut candidates: Vec<_> = instance
+		.enumerate_physical_devices()?
+		.enumerate()
+		.map(|(index, p)| {
+			let missing_extensions = device_extensions.difference(p.supported_extensions());
+
+			let queue_flags: Vec<_> = p
+				.queue_family_properties()
+				.iter()
+				.map(|properties| properties.queue_flags)
+				.collect();
+			let can_present = surface.map(|surface| {
+				let p = &p;
+				move |i| p.surface_support(i, surface).unwrap_or(false)
+			});
+			let (compute_family, present_family) = choose_queue_families(&queue_flags, can_present);
+			let queue_families = compute_family
+				.zip(present_family)
+				.map(|(compute, present)| QueueFamilies { compute, present });
+
+			let status = if missing_extensions != vulkano::device::DeviceExtensions::empty() {
+				DeviceStatus::MissingExtensions(Box::new(missing_extensions))
+			} else if compute_family.is_none() {
+				DeviceStatus::NoComputeQueue
+			} else if present_family.is_none() {
+				DeviceStatus::NoPresentQueue
+			} else {
+				DeviceStatus::NotSelected
+			};
+
+			let properties = p.properties();
+			let report = DeviceReport {
+				index,
+				name: properties.device_name.clone(),
+				device_type: properties.device_type,
+				api_version: properties.api_version,
+				driver_name: properties.driver_name.clone(),
+				status,
+			};
+			Candidate {
+				physical_device: p,
+				queue_families,
+				report,
+			}
+		})
+		.collect();
+
+	let suitable = candidates
+		.iter()
+		.enumerate()
+		.filter(|(_, candidate)| matches!(candidate.report.status, DeviceStatus::NotSelected));
+	let selected = match selection {
+		DeviceSelection::Auto => suitable
+			.min_by_key(|(_, candidate)| match candidate.report.device_type {
+				vulkano::device::physical::PhysicalDeviceType::DiscreteGpu => 0,
+				vulkano::device::physical::PhysicalDeviceType::IntegratedGpu => 1,
+				vulkano::device::physical::PhysicalDeviceType::VirtualGpu => 2,
+				vulkano::device::physical::PhysicalDeviceType::Cpu => 3,
+				vulkano::device::physical::PhysicalDeviceType::Other => 4,
+				_ => 5,
+			})
+			.map(|(i, _)| i),
+		DeviceSelection::Name(name) => {
+			let name = name.to_lowercase();
+			suitable
+				.filter(|(_, candidate)| candidate.report.name.to_lowercase().contains(&name))
+				.map(|(i, _)| i)
+				.next()
+		},
+		DeviceSelection::Index(index) => suitable
+			.filter(|(_, candidate)| candidate.report.index == *index)
+			.map(|(i, _)| i)
+			.next(),
+		DeviceSelection::Type(device_type) => suitable
+			.filter(|(_, candidate)| candidate.report.device_type == *device_type)
+			.map(|(i, _)| i)
+			.next(),
+	};
+	if let Some(selected) = selected {
+		candidates[selected].report.status = DeviceStatus::Selected;
+	}
+
+	Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use vulkano::device::{physical::PhysicalDeviceType, QueueFlags};
+
+	#[test]
+	fn parses_selections() {
+		assert_eq!(DeviceSelection::parse("auto"), Some(DeviceSelection::Auto));
+		assert_eq!(
+			DeviceSelection::parse(" AUTO "),
+			Some(DeviceSelection::Auto)
+		);
+		assert_eq!(DeviceSelection::parse(""), Some(DeviceSelection::Auto));
+		assert_eq!(DeviceSelection::parse("2"), Some(DeviceSelection::Index(2)));
+		for (keyword, device_type) in [
+			("discrete", PhysicalDeviceType::DiscreteGpu),
+			("integrated", PhysicalDeviceType::IntegratedGpu),
+			("virtual", PhysicalDeviceType::VirtualGpu),
+			("cpu", PhysicalDeviceType::Cpu),
+			("Other", PhysicalDeviceType::Other),
+		] {
+			assert_eq!(
+				DeviceSelection::parse(keyword),
+				Some(DeviceSelection::Type(device_type))
+			);
+		}
+		assert_eq!(
+			DeviceSelection::parse("llvmpipe"),
+			Some(DeviceSelection::Name("llvmpipe".to_owned()))
+		);
+		assert_eq!(DeviceSelection::parse("-1"), None);
+		assert_eq!(DeviceSelection::parse("99999999999999999999999"), None);
+	}
+
+	const GRAPHICS: QueueFlags = QueueFlags::GRAPHICS
+		.union(QueueFlags::COMPUTE)
+		.union(QueueFlags::TRANSFER);
+	const COMPUTE: QueueFlags = QueueFlags::COMPUTE.union(QueueFlags::TRANSFER);
+
+	#[test]
+	fn prefers_a_dedicated_compute_family() {
+		let families = [GRAPHICS, QueueFlags::TRANSFER, COMPUTE];
+		let headless = None::<fn(u32) -> bool>;
+		assert_eq!(
+			choose_queue_families(&families, headless),
+			(Some(2), Some(2))
+		);
+		assert_eq!(
+			choose_queue_families(&families[..2], headless),
+			(Some(0), Some(0))
+		);
+		assert_eq!(
+			choose_queue_families(&[QueueFlags::TRANSFER], headless),
+			(None, None)
+		);
+	}
+
+	#[test]
+	fn presents_from_the_compute_family_if_it_can() {
+		let families = [GRAPHICS, COMPUTE];
+		assert_eq!(
+			choose_queue_families(&families, Some(|_| true)),
+			(Some(1), Some(1))
+		);
+		assert_eq!(
+			choose_queue_families(&families, Some(|i| i == 0)),
+			(Some(1), Some(0))
+		);
+		assert_eq!(
+			choose_queue_families(&families, Some(|_| false)),
+			(Some(1), None)
+		);
+	}
+}
@@ -0,0 +1,481 @@
+//! The entity world owned by the engine, built on gecs, and the systems run on it.
+//!
+//! gecs declares a world's archetypes at compile time, so a game creating entities of its own
+//! archetypes declares its world with [`world!`], which adds them to the engine's. Games that
+//! only need the engine's archetypes use [`World`]. [`crate::run`] runs the [`Schedule`]'s
+//! stages around the app's own callbacks: startup systems once after [`crate::App::init`],
+//! fixed-update systems before every [`crate::App::update`], update systems once per frame and
+//! extract systems right before [`crate::App::render`]. Extract systems copy component data into
+//! an [`Extract`], whose buffers the renderer uploads and binds to render graph passes by name.
+
+use gecs::prelude::*;
+use shader::glam::{Vec2, Vec4};
+
+pub use shader::SpriteInstance;
+
+/// The gecs traits and macros for creating, finding and iterating entities.
+pub use gecs::prelude;
+
+/// Name of the extracted buffer of [`SpriteInstance`]s, bound by the `sprites` shader.
+pub const SPRITE_BUFFER: &str = "sprites";
+
+/// Placement of an entity on screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+	/// Pixels from the top left corner.
+	pub position: Vec2,
+	/// Radians, clockwise on screen.
+	pub rotation: f32,
+	pub scale: Vec2,
+}
+
+impl Default for Transform {
+	fn default() -> Self {
+		Self::from_position(Vec2::ZERO)
+	}
+}
+
+impl Transform {
+	pub fn from_position(position: Vec2) -> Self {
+		Self {
+			position,
+			rotation: 0.0,
+			scale: Vec2::ONE,
+		}
+	}
+
+	/// Interpolates from `self` at `alpha = 0` to `other` at `alpha = 1`.
+	pub fn lerp(&self, other: &Self, alpha: f32) -> Self {
+		Self {
+			position: self.position.lerp(other.position, alpha),
+			rotation: self.rotation + (other.rotation - self.rotation) * alpha,
+			scale: self.scale.lerp(other.scale, alpha),
+		}
+	}
+}
+
+/// The [`Transform`] before the latest fixed tick, which rendering interpolates from. Kept up to
+/// date by the built-in fixed-update systems; spawn entities with it equal to their transform.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PreviousTransform(pub Transform);
+
+/// Movement applied to an entity's [`Transform`] every fixed tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Velocity {
+	/// Pixels per second.
+	pub linear: Vec2,
+	/// Radians per second.
+	pub angular: f32,
+}
+
+/// A colored rectangle drawn by the `sprites` shader, centered on the entity's position and
+/// scaled by its transform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+	/// Width and height in pixels.
+	pub size: Vec2,
+	/// Blended over the frame by its alpha.
+	pub color: Vec4,
+}
+
+impl Velocity {
+	/// Moves `transform` by `self` over `delta` seconds.
+	pub fn apply(&self, transform: &mut Transform, delta: f32) {
+		transform.position += self.linear * delta;
+		transform.rotation += self.angular * delta;
+	}
+}
+
+impl Sprite {
+	/// The instance the `sprites` shader draws for `self` at `transform`.
+	pub fn instance(&self, transform: &Transform) -> SpriteInstance {
+		SpriteInstance {
+			position: transform.position,
+			size: self.size * transform.scale,
+			color: self.color,
+			rotation: transform.rotation,
+			_padding: [0.0; 3],
+		}
+	}
+}
+
+/// Declares a gecs world named `EcsWorld` with the engine's archetypes, `StaticSprite` and
+/// `MovingSprite`, followed by the given `ecs_archetype!` declarations, and implements
+/// [`EngineWorld`] for it. Archetypes of the game's own may use the engine's components, which
+/// the built-in systems then process too.
+///
+/// As with `ecs_world!`, the world's queries name its archetypes, so invoke this in a module of
+/// its own with the game's components in scope and glob-import that module where the game runs
+/// `ecs_iter!` and `ecs_find!`. The generated code refers to `::gecs`, which the game must depend
+/// on.
+///
+/// ```ignore
+/// mod world {
+///     use super::Health;
+///     dot::world! {
+///         ecs_archetype!(Enemy, dyn, Transform, PreviousTransform, Velocity, Sprite, Health);
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! world {
+	($($archetypes:tt)*) => {
+		use $crate::ecs::prelude::*;
+		use $crate::ecs::{PreviousTransform, Sprite, Transform, Velocity};
+
+		$crate::ecs::prelude::ecs_world! {
+			ecs_archetype!(StaticSprite, dyn, Transform, PreviousTransform, Sprite);
+			ecs_archetype!(MovingSprite, dyn, Transform, PreviousTransform, Velocity, Sprite);
+			$($archetypes)*
+		}
+
+		impl $crate::ecs::EngineWorld for EcsWorld {
+			fn position(&mut self, entity: EntityAny) -> Option<$crate::glam::Vec2> {
+				ecs_find!(self, entity, |transform: &Transform| transform.position)
+			}
+
+			fn store_previous_transforms(&mut self) {
+				ecs_iter!(self, |transform: &Transform, previous: &mut PreviousTransform| {
+					previous.0 = *transform;
+				});
+			}
+
+			fn apply_velocity(&mut self, delta: f32) {
+				ecs_iter!(self, |transform: &mut Transform, velocity: &Velocity| {
+					velocity.apply(transform, delta);
+				});
+			}
+
+			fn sprite_instances(&mut self, alpha: f32) -> Vec<$crate::ecs::SpriteInstance> {
+				let mut instances = Vec::new();
+				ecs_iter!(self, |transform: &Transform,
+				                 previous: &PreviousTransform,
+				                 sprite: &Sprite| {
+					instances.push(sprite.instance(&previous.0.lerp(transform, alpha)));
+				});
+				instances
+			}
+		}
+	};
+}
+
+/// The queries the engine runs on a world, implemented by [`world!`].
+pub trait EngineWorld: Default + 'static {
+	/// Position of `entity`, or `None` if it has been destroyed or has no [`Transform`].
+	fn position(&mut self, entity: EntityAny) -> Option<Vec2>;
+
+	/// Copies every [`Transform`] into its [`PreviousTransform`].
+	fn store_previous_transforms(&mut self);
+
+	/// Moves every entity with a [`Velocity`] by it over `delta` seconds.
+	fn apply_velocity(&mut self, delta: f32);
+
+	/// Every [`Sprite`] at its transform interpolated by `alpha`.
+	fn sprite_instances(&mut self, alpha: f32) -> Vec<SpriteInstance>;
+}
+
+/// The world of games without archetypes of their own, with only the engine's.
+pub mod default_world {
+	crate::world!();
+}
+
+pub use default_world::{MovingSprite, StaticSprite};
+
+/// The engine's default world.
+pub type World = default_world::EcsWorld;
+
+/// Data extracted from the world for one frame.
+#[derive(Default)]
+pub struct Extract {
+	/// The frame's interpolation alpha, see [`crate::Frame::alpha`].
+	pub alpha: f32,
+	buffers: std::collections::HashMap<String, Vec<u8>>,
+}
+
+impl Extract {
+	/// Appends `data` to the buffer `name`, which passes bind with
+	/// [`crate::graph::Pass::extracted_buffers`].
+	pub fn write<T: bytemuck::Pod>(&mut self, name: &str, data: &[T]) {
+		self.buffers
+			.entry(name.to_owned())
+			.or_default()
+			.extend_from_slice(bytemuck::cast_slice(data));
+	}
+
+	/// Contents of the buffer `name`, empty if nothing was written to it.
+	pub fn buffer(&self, name: &str) -> &[u8] {
+		self.buffers.get(name).map_or(&[], Vec::as_slice)
+	}
+}
+
+type StartupSystem<W> = Box<dyn FnMut(&mut W)>;
+type FixedUpdateSystem<W> = Box<dyn FnMut(&mut W, &crate::Tick)>;
+type UpdateSystem<W> = Box<dyn FnMut(&mut W, std::time::Duration)>;
+type ExtractSystem<W> = Box<dyn FnMut(&mut W, &mut Extract)>;
+
+/// Systems by stage, each stage running its systems in the order they were added. The default
+/// schedule has the built-in systems: [`store_previous_transforms`] and [`apply_velocity`] as
+/// fixed-update systems and [`extract_sprites`] as an extract system.
+pub struct Schedule<W = World> {
+	startup: Vec<StartupSystem<W>>,
+	fixed_update: Vec<FixedUpdateSystem<W>>,
+	update: Vec<UpdateSystem<W>>,
+	extract: Vec<ExtractSystem<W>>,
+}
+
+impl<W: EngineWorld> Default for Schedule<W> {
+	fn default() -> Self {
+		let mut schedule = Self::empty();
+		schedule
+			.add_fixed_update_system(store_previous_transforms)
+			.add_fixed_update_system(apply_velocity)
+			.add_extract_system(extract_sprites);
+		schedule
+	}
+}
+
+impl<W: EngineWorld> Schedule<W> {
+	/// A schedule without the built-in systems.
+	pub fn empty() -> Self {
+		Self {
+			startup: Vec::new(),
+			fixed_update: Vec::new(),
+			update: Vec::new(),
+			extract: Vec::new(),
+		}
+	}
+
+	/// Adds a system run once, before the first update.
+	pub fn add_startup_system(&mut self, system: impl FnMut(&mut W) + 'static) -> &mut Self {
+		self.startup.push(Box::new(system));
+		self
+	}
+
+	/// Adds a system run every fixed tick, before [`crate::App::update`].
+	pub fn add_fixed_update_system(
+		&mut self,
+		system: impl FnMut(&mut W, &crate::Tick) + 'static,
+	) -> &mut Self {
+		self.fixed_update.push(Box::new(system));
+		self
+	}
+
+	/// Adds a system run once per frame after the fixed ticks, with the time since the previous
+	/// frame.
+	pub fn add_update_system(
+		&mut self,
+		system: impl FnMut(&mut W, std::time::Duration) + 'static,
+	) -> &mut Self {
+		self.update.push(Box::new(system));
+		self
+	}
+
+	/// Adds a system run once per frame before rendering, copying component data into the
+	/// frame's [`Extract`].
+	pub fn add_extract_system(
+		&mut self,
+		system: impl FnMut(&mut W, &mut Extract) + 'static,
+	) -> &mut Self {
+		self.extract.push(Box::new(system));
+		self
+	}
+
+	/// Runs the startup systems added since the last call, then drops them.
+	pub(crate) fn run_startup(&mut self, world: &mut W) {
+		for mut system in std::mem::take(&mut self.startup) {
+			system(world);
+		}
+	}
+
+	pub(crate) fn run_fixed_update(&mut self, world: &mut W, tick: &crate::Tick) {
+		for system in &mut self.fixed_update {
+			system(world, tick);
+		}
+	}
+
+	pub(crate) fn run_update(&mut self, world: &mut W, delta: std::time::Duration) {
+		for system in &mut self.update {
+			system(world, delta);
+		}
+	}
+
+	pub(crate) fn run_extract(&mut self, world: &mut W, alpha: f32) -> Extract {
+		let mut extract = Extract {
+			alpha,
+			..Default::default()
+		};
+		for system in &mut self.extract {
+			system(world, &mut extract);
+		}
+		extract
+	}
+}
+
+/// Position of `entity`, or `None` if it has been destroyed or has no [`Transform`].
+pub fn position<W: EngineWorld>(world: &mut W, entity: EntityAny) -> Option<Vec2> {
+	world.position(entity)
+}
+
+/// Copies every [`Transform`] into its [`PreviousTransform`] at the start of a tick.
+pub fn store_previous_transforms<W: EngineWorld>(world: &mut W, _tick: &crate::Tick) {
+	world.store_previous_transforms();
+}
+
+/// Moves every entity by its [`Velocity`] over one tick.
+pub fn apply_velocity<W: EngineWorld>(world: &mut W, tick: &crate::Tick) {
+	world.apply_velocity(tick.delta.as_secs_f32());
+}
+
+/// Writes every [`Sprite`] at its interpolated transform to [`SPRITE_BUFFER`].
+pub fn extract_sprites<W: EngineWorld>(world: &mut W, extract: &mut Extract) {
+	let instances = world.sprite_instances(extract.alpha);
+	extract.write(SPRITE_BUFFER, &instances);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	// Shadowed by the `World` alias in the glob import.
+	use gecs::prelude::World as _;
+	use shader::glam::{vec2, vec4};
+	use std::{cell::RefCell, rc::Rc, time::Duration};
+
+	fn instance_at(bytes: &[u8], index: usize) -> SpriteInstance {
+		let size = std::mem::size_of::<SpriteInstance>();
+		bytemuck::pod_read_unaligned(&bytes[index * size..][..size])
+	}
+
+	#[test]
+	fn runs_stages_in_order() {
+		let log = Rc::new(RefCell::new(Vec::new()));
+		let mut schedule = Schedule::<World>::empty();
+		let entry = {
+			let log = log.clone();
+			move |entry: String| log.borrow_mut().push(entry)
+		};
+		let (a, b, c, d, e) = (
+			entry.clone(),
+			entry.clone(),
+			entry.clone(),
+			entry.clone(),
+			entry,
+		);
+		schedule
+			.add_extract_system(move |_, extract| a(format!("extract {}", extract.alpha)))
+			.add_update_system(move |_, delta| b(format!("update {delta:?}")))
+			.add_fixed_update_system(move |_, tick| c(format!("fixed {}", tick.index)))
+			.add_fixed_update_system(move |_, tick| d(format!("fixed {} again", tick.index)))
+			.add_startup_system(move |_| e("startup".to_owned()));
+
+		// As the engine runs them: startup once, then per frame the ticks due, update and extract.
+		let mut world = World::default();
+		let mut game_loop = crate::GameLoop::new(crate::LoopConfig {
+			tick_rate: 10.0,
+			..Default::default()
+		})
+		.unwrap();
+		schedule.run_startup(&mut world);
+		for _ in 0..2 {
+			let alpha = game_loop.advance_by(Duration::from_millis(150), |tick| {
+				schedule.run_fixed_update(&mut world, tick)
+			});
+			schedule.run_update(&mut world, game_loop.frame_time());
+			schedule.run_extract(&mut world, alpha);
+		}
+		schedule.run_startup(&mut world);
+
+		assert_eq!(
+			*log.borrow(),
+			[
+				"startup",
+				"fixed 0",
+				"fixed 0 again",
+				"update 150ms",
+				"extract 0.5",
+				"fixed 1",
+				"fixed 1 again",
+				"fixed 2",
+				"fixed 2 again",
+				"update 150ms",
+				"extract 0",
+			]
+		);
+	}
+
+	#[test]
+	fn appends_to_named_buffers() {
+		let mut extract = Extract::default();
+		extract.write("a", &[1_u32, 2]);
+		extract.write("b", &[3_u8]);
+		extract.write("a", &[4_u32]);
+		assert_eq!(
+			extract.buffer("a"),
+			bytemuck::cast_slice::<u32, u8>(&[1, 2, 4])
+		);
+		assert_eq!(extract.buffer("b"), [3]);
+		assert!(extract.buffer("c").is_empty());
+	}
+
+	#[test]
+	fn lerps_transforms() {
+		let from = Transform::from_position(vec2(0.0, 10.0));
+		let to = Transform {
+			position: vec2(4.0, 30.0),
+			rotation: 2.0,
+			scale: vec2(3.0, 1.0),
+		};
+		assert_eq!(from.lerp(&to, 0.0), from);
+		assert_eq!(from.lerp(&to, 1.0), to);
+		assert_eq!(
+			from.lerp(&to, 0.25),
+			Transform {
+				position: vec2(1.0, 15.0),
+				rotation: 0.5,
+				scale: vec2(1.5, 1.0),
+			}
+		);
+	}
+
+	#[test]
+	fn extracts_interpolated_sprites() {
+		let mut world = World::default();
+		let transform = Transform {
+			scale: vec2(2.0, 2.0),
+			..Transform::from_position(vec2(10.0, 20.0))
+		};
+		let entity = world.create::<MovingSprite>((
+			transform,
+			PreviousTransform(transform),
+			Velocity {
+				linear: vec2(40.0, 0.0),
+				angular: 1.0,
+			},
+			Sprite {
+				size: vec2(8.0, 4.0),
+				color: vec4(1.0, 0.5, 0.25, 1.0),
+			},
+		));
+
+		let mut schedule = Schedule::default();
+		schedule.run_fixed_update(
+			&mut world,
+			&crate::Tick {
+				index: 0,
+				delta: Duration::from_millis(250),
+			},
+		);
+		let extract = schedule.run_extract(&mut world, 0.5);
+
+		// One std430 instance of 48 bytes per sprite.
+		let bytes = extract.buffer(SPRITE_BUFFER);
+		assert_eq!(std::mem::size_of::<SpriteInstance>(), 48);
+		assert_eq!(bytes.len(), 48);
+		// Halfway between the transform before the tick and after it.
+		let instance = instance_at(bytes, 0);
+		assert_eq!(instance.position, vec2(15.0, 20.0));
+		assert_eq!(instance.rotation, 0.125);
+		assert_eq!(instance.size, vec2(16.0, 8.0));
+		assert_eq!(instance.color, vec4(1.0, 0.5, 0.25, 1.0));
+		assert_eq!(position(&mut world, entity.into()), Some(vec2(20.0, 20.0)));
+	}
+}
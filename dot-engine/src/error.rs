@@ -0,0 +1,206 @@
+/// Errors returned while creating a [`crate::Renderer`] or rendering a frame.
+#[derive(Debug)]
+pub enum RendererError {
+	/// The Vulkan library could not be loaded.
+	Library(vulkano::LoadingError),
+	/// No physical device has the required extensions and a suitable queue family, or none
+	/// matched the selection policy. Lists why each device was rejected.
+	NoSuitableDevice(Vec<crate::device::DeviceReport>),
+	/// The surface supports none of these formats in a way the renderer can write to, directly
+	/// or through an intermediate image.
+	NoSurfaceFormat(Vec<(vulkano::format::Format, vulkano::swapchain::ColorSpace)>),
+	/// The shader module has no entry point with this name.
+	MissingEntryPoint(String),
+	/// A render graph pass reads a transient image that no earlier pass writes.
+	UnwrittenImage { pass: String, image: String },
+	/// A render graph pass binds something at a binding its shader doesn't declare.
+	MissingBinding { pass: String, binding: u32 },
+	/// A render graph pass's shader declares a binding that nothing is bound to.
+	UnboundBinding { pass: String, binding: u32 },
+	/// A render graph pass binds a different kind of resource than its shader declares.
+	BindingTypeMismatch {
+		pass: String,
+		binding: u32,
+		bound: crate::reflect::DescriptorKind,
+		declared: crate::reflect::DescriptorKind,
+	},
+	/// The shader module was rejected before creating the pipelines.
+	InvalidShader(crate::reflect::SpirvError),
+	/// The descriptor set layouts reflected from the shader could not form a pipeline layout.
+	PipelineLayout(vulkano::pipeline::layout::IntoPipelineLayoutCreateInfoError),
+	/// Creating the shader module, pipeline layout or compute pipeline failed.
+	Pipeline(vulkano::Validated<vulkano::VulkanError>),
+	/// Creating or recreating the swapchain failed.
+	Swapchain(vulkano::Validated<vulkano::VulkanError>),
+	/// Acquiring the next swapchain image failed for a reason other than it being out of date.
+	AcquireImage(vulkano::Validated<vulkano::VulkanError>),
+	/// Allocating the offscreen target failed.
+	ImageAllocation(vulkano::Validated<vulkano::image::AllocateImageError>),
+	/// Allocating a buffer failed.
+	BufferAllocation(vulkano::Validated<vulkano::buffer::AllocateBufferError>),
+	/// Allocating per-frame data from the subbuffer allocator failed.
+	SubbufferAllocation(vulkano::memory::allocator::MemoryAllocatorError),
+	/// Submitting a command buffer failed.
+	Execute(vulkano::command_buffer::CommandBufferExecError),
+	/// Reading a buffer back on the host failed.
+	HostAccess(vulkano::sync::HostAccessError),
+	/// The render target can't be copied from, so frames can't be captured.
+	CaptureNotSupported,
+	/// An offscreen frame was requested from a renderer drawing to a window.
+	NotHeadless,
+	/// Captured frames in this format can't be converted to RGBA8.
+	UnsupportedCaptureFormat(vulkano::format::Format),
+	/// Recording a command failed validation.
+	Validation(Box<vulkano::ValidationError>),
+	/// Any other Vulkan call failed.
+	Vulkan(vulkano::Validated<vulkano::VulkanError>),
+}
+
+impl std::fmt::Display for RendererError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Library(_) => write!(f, "failed to load the Vulkan library"),
+			Self::NoSuitableDevice(devices) => {
+				write!(f, "no suitable physical device found")?;
+				for device in devices {
+					write!(f, "; {device}")?;
+				}
+				Ok(())
+			},
+			Self::NoSurfaceFormat(formats) => {
+				write!(f, "no usable surface format among {formats:?}")
+			},
+			Self::MissingEntryPoint(name) => write!(f, "shader has no entry point named {name:?}"),
+			Self::UnwrittenImage { pass, image } => write!(
+				f,
+				"pass {pass:?} reads image {image:?} before any pass writes it"
+			),
+			Self::MissingBinding { pass, binding } => write!(
+				f,
+				"pass {pass:?} binds binding {binding}, which its shader doesn't declare"
+			),
+			Self::UnboundBinding { pass, binding } => write!(
+				f,
+				"the shader of pass {pass:?} declares binding {binding}, which nothing is bound to"
+			),
+			Self::BindingTypeMismatch {
+				pass,
+				binding,
+				bound,
+				declared,
+			} => write!(
+				f,
+				"pass {pass:?} binds a {bound:?} at binding {binding}, but its shader declares a \
+				 {declared:?}"
+			),
+			Self::InvalidShader(_) => write!(f, "invalid shader module"),
+			Self::PipelineLayout(_) => write!(f, "failed to create the pipeline layout"),
+			Self::Pipeline(_) => write!(f, "failed to create the compute pipeline"),
+			Self::Swapchain(_) => write!(f, "failed to create the swapchain"),
+			Self::AcquireImage(_) => write!(f, "failed to acquire the next swapchain image"),
+			Self::ImageAllocation(_) => write!(f, "failed to allocate an image"),
+			Self::BufferAllocation(_) => write!(f, "failed to allocate a buffer"),
+			Self::SubbufferAllocation(_) => write!(f, "failed to allocate per-frame data"),
+			Self::Execute(_) => write!(f, "failed to execute a command buffer"),
+			Self::HostAccess(_) => write!(f, "failed to read a buffer on the host"),
+			Self::CaptureNotSupported => {
+				write!(f, "the render target does not support copying frames")
+			},
+			Self::NotHeadless => write!(f, "the renderer has no offscreen image"),
+			Self::UnsupportedCaptureFormat(format) => {
+				write!(f, "cannot capture frames in format {format:?}")
+			},
+			Self::Validation(_) => write!(f, "a command failed validation"),
+			Self::Vulkan(_) => write!(f, "a Vulkan call failed"),
+		}
+	}
+}
+
+impl std::error::Error for RendererError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Library(e) => Some(e),
+			Self::PipelineLayout(e) => Some(e),
+			Self::Pipeline(e) | Self::Swapchain(e) | Self::AcquireImage(e) | Self::Vulkan(e) => {
+				Some(e)
+			},
+			Self::ImageAllocation(e) => Some(e),
+			Self::BufferAllocation(e) => Some(e),
+			Self::SubbufferAllocation(e) => Some(e),
+			Self::Execute(e) => Some(e),
+			Self::HostAccess(e) => Some(e),
+			Self::Validation(e) => Some(e.as_ref()),
+			Self::InvalidShader(e) => Some(e),
+			Self::NoSuitableDevice(_)
+			| Self::NoSurfaceFormat(_)
+			| Self::MissingEntryPoint(_)
+			| Self::UnwrittenImage { .. }
+			| Self::MissingBinding { .. }
+			| Self::UnboundBinding { .. }
+			| Self::BindingTypeMismatch { .. }
+			| Self::CaptureNotSupported
+			| Self::NotHeadless
+			| Self::UnsupportedCaptureFormat(_) => None,
+		}
+	}
+}
+
+impl From<vulkano::LoadingError> for RendererError {
+	fn from(e: vulkano::LoadingError) -> Self {
+		Self::Library(e)
+	}
+}
+
+impl From<crate::reflect::SpirvError> for RendererError {
+	fn from(e: crate::reflect::SpirvError) -> Self {
+		Self::InvalidShader(e)
+	}
+}
+
+impl From<vulkano::Validated<vulkano::VulkanError>> for RendererError {
+	fn from(e: vulkano::Validated<vulkano::VulkanError>) -> Self {
+		Self::Vulkan(e)
+	}
+}
+
+impl From<vulkano::VulkanError> for RendererError {
+	fn from(e: vulkano::VulkanError) -> Self {
+		Self::Vulkan(vulkano::Validated::Error(e))
+	}
+}
+
+impl From<vulkano::Validated<vulkano::image::AllocateImageError>> for RendererError {
+	fn from(e: vulkano::Validated<vulkano::image::AllocateImageError>) -> Self {
+		Self::ImageAllocation(e)
+	}
+}
+
+impl From<vulkano::Validated<vulkano::buffer::AllocateBufferError>> for RendererError {
+	fn from(e: vulkano::Validated<vulkano::buffer::AllocateBufferError>) -> Self {
+		Self::BufferAllocation(e)
+	}
+}
+
+impl From<vulkano::memory::allocator::MemoryAllocatorError> for RendererError {
+	fn from(e: vulkano::memory::allocator::MemoryAllocatorError) -> Self {
+		Self::SubbufferAllocation(e)
+	}
+}
+
+impl From<vulkano::command_buffer::CommandBufferExecError> for RendererError {
+	fn from(e: vulkano::command_buffer::CommandBufferExecError) -> Self {
+		Self::Execute(e)
+	}
+}
+
+impl From<vulkano::sync::HostAccessError> for RendererError {
+	fn from(e: vulkano::sync::HostAccessError) -> Self {
+		Self::HostAccess(e)
+	}
+}
+
+impl From<Box<vulkano::ValidationError>> for RendererError {
+	fn from(e: Box<vulkano::ValidationError>) -> Self {
+		Self::Validation(e)
+	}
+}
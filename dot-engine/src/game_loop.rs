@@ -0,0 +1,366 @@
+//! Fixed-timestep updates with variable-rate rendering.
+//!
+//! Each frame, the time since the previous one is added to an accumulator and [`App::update`]
+//! runs once per whole tick in it. [`App::render`] then gets the fraction of a tick left over as
+//! [`Frame::alpha`], to interpolate between the previous and current simulation states. After a
+//! stall, the frame time is clamped and at most [`LoopConfig::max_ticks_per_frame`] ticks run, so
+//! a slow update can't make every following frame slower still.
+
+/// A game or demo run by [`crate::run`], driven by [`GameLoop`].
+pub trait App<W: crate::ecs::EngineWorld = crate::ecs::World> {
+	/// Called once the renderer exists, before the first update. Usually sets the render graph
+	/// with [`crate::Renderer::set_graph`], spawns entities and adds systems.
+	fn init(&mut self, init: Init<'_, W>) -> Result<(), crate::RendererError>;
+
+	/// Advances the simulation by one fixed tick, after the fixed-update systems.
+	fn update(&mut self, update: Update<'_, W>);
+
+	/// Draws the current state. Called once per frame, after any updates.
+	fn render(&mut self, frame: Frame<'_, W>) -> Result<(), crate::RendererError>;
+
+	/// Called for every input event, right after [`Update::input`] has applied it. Most apps read
+	/// the input state instead. Replays make the same calls as the recorded run, see
+	/// [`crate::replay`].
+	fn input_event(&mut self, _event: &crate::input::InputEvent) {}
+}
+
+/// Passed to [`App::init`].
+pub struct Init<'a, W = crate::ecs::World> {
+	pub renderer: &'a mut crate::Renderer,
+	pub world: &'a mut W,
+	pub schedule: &'a mut crate::ecs::Schedule<W>,
+	pub audio: &'a mut crate::audio::Audio,
+	/// Usually has its [`crate::input::Input::actions`] set here.
+	pub input: &'a mut crate::input::Input,
+	/// `None` when headless.
+	pub window: Option<&'a mut crate::window::Window>,
+}
+
+/// Passed to [`App::update`].
+pub struct Update<'a, W = crate::ecs::World> {
+	pub world: &'a mut W,
+	pub audio: &'a mut crate::audio::Audio,
+	/// Mutable to rumble gamepads.
+	pub input: &'a mut crate::input::Input,
+	pub tick: &'a Tick,
+}
+
+/// A fixed tick, passed to fixed-update systems and [`App::update`].
+pub struct Tick {
+	/// Number of ticks before this one.
+	pub index: u64,
+	/// Simulated time per tick, [`LoopConfig::tick_duration`].
+	pub delta: std::time::Duration,
+}
+
+/// Passed to [`App::render`].
+pub struct Frame<'a, W = crate::ecs::World> {
+	pub renderer: &'a mut crate::Renderer,
+	pub world: &'a mut W,
+	/// `None` when headless.
+	pub window: Option<&'a mut crate::window::Window>,
+	pub image_extent: [u32; 2],
+	/// How far the current time is between the last tick and the next one, in `[0, 1)`.
+	pub alpha: f32,
+}
+
+/// How the loop spaces out frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FramePacing {
+	/// Render as often as presenting allows.
+	Unlimited,
+	/// Render at most this many frames per second, sleeping in between.
+	Limit(f64),
+}
+
+#[derive(Clone, Debug)]
+pub struct LoopConfig {
+	/// Updates per second.
+	pub tick_rate: f64,
+	/// Most updates run in one frame. Time for further ticks is dropped, slowing the simulation
+	/// down rather than falling further behind.
+	pub max_ticks_per_frame: u32,
+	/// Longest time between frames that is simulated, e.g. after the window was dragged or the
+	/// process stopped in a debugger.
+	pub max_frame_time: std::time::Duration,
+	pub pacing: FramePacing,
+}
+
+impl Default for LoopConfig {
+	fn default() -> Self {
+		Self {
+			tick_rate: 60.0,
+			max_ticks_per_frame: 5,
+			max_frame_time: std::time::Duration::from_millis(250),
+			pacing: FramePacing::Unlimited,
+		}
+	}
+}
+
+impl LoopConfig {
+	/// Panics unless the config is valid, see [`Self::validate`].
+	pub fn tick_duration(&self) -> std::time::Duration {
+		std::time::Duration::from_secs_f64(1.0 / self.tick_rate)
+	}
+
+	/// Checks that ticks last at least a nanosecond, that frames can run at least one of them and
+	/// that the frame rate limit, if any, is a positive number.
+	pub fn validate(&self) -> Result<(), LoopConfigError> {
+		if !(self.tick_rate.is_finite() && self.tick_rate > 0.0) {
+			return Err(LoopConfigError::TickRate(self.tick_rate));
+		}
+		if 1.0 / self.tick_rate < 1e-9 {
+			return Err(LoopConfigError::TickTooShort(self.tick_rate));
+		}
+		if self.max_ticks_per_frame == 0 {
+			return Err(LoopConfigError::NoTicksPerFrame);
+		}
+		if self.max_frame_time < self.tick_duration() {
+			return Err(LoopConfigError::FrameTimeTooShort(self.max_frame_time));
+		}
+		if let FramePacing::Limit(fps) = self.pacing {
+			if !(fps.is_finite() && fps > 0.0) {
+				return Err(LoopConfigError::FrameRateLimit(fps));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Why a [`LoopConfig`] was rejected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopConfigError {
+	/// The tick rate isn't a positive number.
+	TickRate(f64),
+	/// Ticks at this rate would be shorter than a nanosecond.
+	TickTooShort(f64),
+	/// `max_ticks_per_frame` is zero, so no tick would ever run.
+	NoTicksPerFrame,
+	/// `max_frame_time` is shorter than a tick, so no tick would ever run.
+	FrameTimeTooShort(std::time::Duration),
+	/// The frame rate limit isn't a positive number.
+	FrameRateLimit(f64),
+}
+
+impl std::fmt::Display for LoopConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TickRate(rate) => write!(f, "tick rate {rate} is not a positive number"),
+			Self::TickTooShort(rate) => {
+				write!(f, "tick rate {rate} makes ticks shorter than a nanosecond")
+			},
+			Self::NoTicksPerFrame => write!(f, "max ticks per frame is zero"),
+			Self::FrameTimeTooShort(time) => {
+				write!(f, "max frame time {time:?} is shorter than a tick")
+			},
+			Self::FrameRateLimit(fps) => {
+				write!(f, "frame rate limit {fps} is not a positive number")
+			},
+		}
+	}
+}
+
+impl std::error::Error for LoopConfigError {}
+
+/// Loop state, advanced once per rendered frame.
+pub struct GameLoop {
+	config: LoopConfig,
+	accumulator: std::time::Duration,
+	last_frame: std::time::Instant,
+	/// Time between the last two frames, after clamping to `max_frame_time`.
+	frame_time: std::time::Duration,
+	next_tick: u64,
+}
+
+impl GameLoop {
+	pub fn new(config: LoopConfig) -> Result<Self, LoopConfigError> {
+		config.validate()?;
+		Ok(Self {
+			config,
+			accumulator: std::time::Duration::ZERO,
+			last_frame: std::time::Instant::now(),
+			frame_time: std::time::Duration::ZERO,
+			next_tick: 0,
+		})
+	}
+
+	/// Calls `update` for each tick due since the previous frame and returns the interpolation
+	/// alpha for rendering this one.
+	pub fn advance(&mut self, update: impl FnMut(&Tick)) -> f32 {
+		let now = std::time::Instant::now();
+		let frame_time = now - self.last_frame;
+		self.last_frame = now;
+		self.advance_by(frame_time, update)
+	}
+
+	/// Like [`Self::advance`], with the time since the previous frame given instead of measured,
+	/// e.g. to replay a recording.
+	pub fn advance_by(
+		&mut self,
+		frame_time: std::time::Duration,
+		mut update: impl FnMut(&Tick),
+	) -> f32 {
+		self.frame_time = frame_time.min(self.config.max_frame_time);
+		self.accumulator += self.frame_time;
+
+		let delta = self.config.tick_duration();
+		let mut ticks = 0;
+		while self.accumulator >= delta {
+			if ticks == self.config.max_ticks_per_frame {
+				self.accumulator = std::time::Duration::from_nanos(
+					(self.accumulator.as_nanos() % delta.as_nanos()) as u64,
+				);
+				break;
+			}
+			update(&Tick {
+				index: self.next_tick,
+				delta,
+			});
+			self.accumulator -= delta;
+			self.next_tick += 1;
+			ticks += 1;
+		}
+		(self.accumulator.as_secs_f64() / delta.as_secs_f64()) as f32
+	}
+
+	/// Time simulated by the latest call to [`Self::advance`].
+	pub fn frame_time(&self) -> std::time::Duration {
+		self.frame_time
+	}
+
+	/// When the next frame should start, or `None` to render continuously.
+	pub fn next_frame_at(&self) -> Option<std::time::Instant> {
+		match self.config.pacing {
+			FramePacing::Unlimited => None,
+			FramePacing::Limit(fps) => {
+				Some(self.last_frame + std::time::Duration::from_secs_f64(1.0 / fps))
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	fn game_loop(tick_rate: f64) -> GameLoop {
+		GameLoop::new(LoopConfig {
+			tick_rate,
+			..LoopConfig::default()
+		})
+		.unwrap()
+	}
+
+	/// Advances by `frame_time`, returning the alpha and the indices of the ticks run.
+	fn advance(game_loop: &mut GameLoop, frame_time: Duration) -> (f32, Vec<u64>) {
+		let mut ticks = Vec::new();
+		let alpha = game_loop.advance_by(frame_time, |tick| ticks.push(tick.index));
+		(alpha, ticks)
+	}
+
+	#[test]
+	fn runs_whole_ticks_and_carries_the_rest() {
+		let mut game_loop = game_loop(10.0);
+		let (alpha, ticks) = advance(&mut game_loop, Duration::from_millis(250));
+		assert_eq!(ticks, [0, 1]);
+		assert!((alpha - 0.5).abs() < 1e-6);
+
+		let (alpha, ticks) = advance(&mut game_loop, Duration::from_millis(60));
+		assert_eq!(ticks, [2]);
+		assert!((alpha - 0.1).abs() < 1e-6);
+
+		let (alpha, ticks) = advance(&mut game_loop, Duration::from_millis(20));
+		assert!(ticks.is_empty());
+		assert!((alpha - 0.3).abs() < 1e-6);
+	}
+
+	#[test]
+	fn passes_the_tick_duration() {
+		let mut game_loop = game_loop(50.0);
+		let mut deltas = Vec::new();
+		game_loop.advance_by(Duration::from_millis(40), |tick| deltas.push(tick.delta));
+		assert_eq!(deltas, [Duration::from_millis(20); 2]);
+	}
+
+	#[test]
+	fn clamps_long_frames() {
+		let mut game_loop = GameLoop::new(LoopConfig {
+			tick_rate: 100.0,
+			max_ticks_per_frame: 100,
+			max_frame_time: Duration::from_millis(250),
+			pacing: FramePacing::Unlimited,
+		})
+		.unwrap();
+		let (_, ticks) = advance(&mut game_loop, Duration::from_secs(10));
+		assert_eq!(ticks.len(), 25);
+		assert_eq!(game_loop.frame_time(), Duration::from_millis(250));
+	}
+
+	#[test]
+	fn drops_ticks_beyond_the_limit() {
+		let mut game_loop = GameLoop::new(LoopConfig {
+			tick_rate: 100.0,
+			max_ticks_per_frame: 5,
+			max_frame_time: Duration::from_secs(1),
+			pacing: FramePacing::Unlimited,
+		})
+		.unwrap();
+		let (alpha, ticks) = advance(&mut game_loop, Duration::from_millis(125));
+		assert_eq!(ticks, [0, 1, 2, 3, 4]);
+		assert!((alpha - 0.5).abs() < 1e-6);
+
+		// The dropped ticks don't run later: the next frame only runs its own.
+		let (_, ticks) = advance(&mut game_loop, Duration::from_millis(10));
+		assert_eq!(ticks, [5]);
+	}
+
+	#[test]
+	fn rejects_invalid_configs() {
+		let config = |tick_rate, pacing| LoopConfig {
+			tick_rate,
+			pacing,
+			..LoopConfig::default()
+		};
+		for tick_rate in [0.0, -60.0, f64::NAN, f64::INFINITY] {
+			assert!(matches!(
+				GameLoop::new(config(tick_rate, FramePacing::Unlimited)),
+				Err(LoopConfigError::TickRate(_))
+			));
+		}
+		assert!(matches!(
+			GameLoop::new(config(1e10, FramePacing::Unlimited)),
+			Err(LoopConfigError::TickTooShort(_))
+		));
+		assert!(GameLoop::new(config(1e9, FramePacing::Unlimited)).is_ok());
+		assert!(matches!(
+			GameLoop::new(config(60.0, FramePacing::Limit(0.0))),
+			Err(LoopConfigError::FrameRateLimit(_))
+		));
+	}
+
+	#[test]
+	fn rejects_configs_that_never_tick() {
+		let config = LoopConfig {
+			max_ticks_per_frame: 0,
+			..LoopConfig::default()
+		};
+		assert!(matches!(
+			GameLoop::new(config),
+			Err(LoopConfigError::NoTicksPerFrame)
+		));
+
+		let config = |max_frame_time| LoopConfig {
+			tick_rate: 100.0,
+			max_frame_time,
+			..LoopConfig::default()
+		};
+		for max_frame_time in [Duration::ZERO, Duration::from_millis(9)] {
+			assert!(matches!(
+				GameLoop::new(config(max_frame_time)),
+				Err(LoopConfigError::FrameTimeTooShort(_))
+			));
+		}
+		assert!(GameLoop::new(config(Duration::from_millis(10))).is_ok());
+	}
+}
@@ -0,0 +1,392 @@
+//! Gamepad devices, read by a [`GamepadBackend`] into the engine's [`crate::input::Input`].
+//!
+//! On Linux, [`default_backend`] reads gamepads from evdev, noticing ones plugged in while running
+//! and rumbling them if their driver supports it. Other platforms have no backend yet, so no
+//! gamepad ever connects. [`FakeGamepads`] is a backend driven by the caller instead, to script
+//! or replay gamepad input in tests.
+
+#[cfg(target_os = "linux")]
+mod evdev;
+
+/// Identifies a gamepad for as long as it stays connected. A gamepad that is plugged back in gets
+/// a new id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadId(pub u32);
+
+/// Buttons by their position on the gamepad, e.g. [`Self::South`] is A on an Xbox controller and
+/// cross on a PlayStation one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+	South,
+	East,
+	North,
+	West,
+	LeftShoulder,
+	RightShoulder,
+	/// Pressed when the trigger is pulled more than halfway, see [`GamepadAxis::LeftTrigger`].
+	LeftTrigger,
+	RightTrigger,
+	Select,
+	Start,
+	/// The button with the vendor's logo.
+	Mode,
+	LeftStick,
+	RightStick,
+	DPadUp,
+	DPadDown,
+	DPadLeft,
+	DPadRight,
+}
+
+impl GamepadButton {
+	pub const ALL: [Self; 17] = [
+		Self::South,
+		Self::East,
+		Self::North,
+		Self::West,
+		Self::LeftShoulder,
+		Self::RightShoulder,
+		Self::LeftTrigger,
+		Self::RightTrigger,
+		Self::Select,
+		Self::Start,
+		Self::Mode,
+		Self::LeftStick,
+		Self::RightStick,
+		Self::DPadUp,
+		Self::DPadDown,
+		Self::DPadLeft,
+		Self::DPadRight,
+	];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+	/// From -1 to 1, left to right.
+	LeftX,
+	/// From -1 to 1, up to down like screen coordinates.
+	LeftY,
+	RightX,
+	RightY,
+	/// From 0 released to 1 fully pulled.
+	LeftTrigger,
+	RightTrigger,
+}
+
+impl GamepadAxis {
+	pub const ALL: [Self; 6] = [
+		Self::LeftX,
+		Self::LeftY,
+		Self::RightX,
+		Self::RightY,
+		Self::LeftTrigger,
+		Self::RightTrigger,
+	];
+
+	pub fn is_trigger(&self) -> bool {
+		matches!(self, Self::LeftTrigger | Self::RightTrigger)
+	}
+}
+
+/// A change reported by a [`GamepadBackend`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+	Connected {
+		gamepad: GamepadId,
+		name: String,
+	},
+	Disconnected {
+		gamepad: GamepadId,
+	},
+	Button {
+		gamepad: GamepadId,
+		button: GamepadButton,
+		pressed: bool,
+	},
+	/// `value` is in the range of the axis, before deadzones are applied.
+	Axis {
+		gamepad: GamepadId,
+		axis: GamepadAxis,
+		value: f32,
+	},
+}
+
+/// Vibration of a gamepad's two motors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rumble {
+	/// Magnitude of the low-frequency motor, from 0 to 1.
+	pub strong: f32,
+	/// Magnitude of the high-frequency motor, from 0 to 1.
+	pub weak: f32,
+	pub duration: std::time::Duration,
+}
+
+/// A source of gamepads, polled once per frame.
+pub trait GamepadBackend {
+	/// Returns the changes since the last call, in the order they happened.
+	fn poll(&mut self) -> Vec<GamepadEvent>;
+
+	/// Starts vibrating `gamepad`, replacing any rumble still playing on it.
+	fn rumble(&mut self, gamepad: GamepadId, rumble: Rumble) -> Result<(), GamepadError>;
+}
+
+/// The platform's backend, if it has one.
+#[cfg(target_os = "linux")]
+pub fn default_backend() -> Option<Box<dyn GamepadBackend>> {
+	Some(Box::new(evdev::EvdevBackend::spawn()))
+}
+
+/// The platform's backend, if it has one.
+#[cfg(not(target_os = "linux"))]
+pub fn default_backend() -> Option<Box<dyn GamepadBackend>> {
+	None
+}
+
+#[derive(Debug)]
+pub enum GamepadError {
+	/// The gamepad has been disconnected, or never was connected.
+	NotConnected(GamepadId),
+	/// The gamepad's driver can't rumble it.
+	RumbleNotSupported(GamepadId),
+	/// Writing to the gamepad's device failed.
+	Io(std::io::Error),
+}
+
+impl std::fmt::Display for GamepadError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::NotConnected(gamepad) => write!(f, "gamepad {} is not connected", gamepad.0),
+			Self::RumbleNotSupported(gamepad) => {
+				write!(f, "gamepad {} does not support rumble", gamepad.0)
+			},
+			Self::Io(_) => write!(f, "failed to write to the gamepad"),
+		}
+	}
+}
+
+impl std::error::Error for GamepadError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::NotConnected(_) | Self::RumbleNotSupported(_) => None,
+			Self::Io(e) => Some(e),
+		}
+	}
+}
+
+impl From<std::io::Error> for GamepadError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+/// A backend whose gamepads are connected and operated by calling its methods, which queue events
+/// for the next poll. Clones share the same gamepads, so a test can keep one to drive the clone
+/// given to [`crate::input::Input::set_gamepad_backend`]. Every fake gamepad supports rumble.
+#[derive(Clone, Default)]
+pub struct FakeGamepads {
+	state: std::sync::Arc<std::sync::Mutex<FakeState>>,
+}
+
+#[derive(Default)]
+struct FakeState {
+	events: std::collections::VecDeque<GamepadEvent>,
+	connected: std::collections::HashSet<GamepadId>,
+	next_id: u32,
+	rumbles: Vec<(GamepadId, Rumble)>,
+}
+
+impl FakeGamepads {
+	/// A backend that replays `events`, e.g. ones recorded from a real backend, on its first poll.
+	pub fn replay(events: impl IntoIterator<Item = GamepadEvent>) -> Self {
+		let gamepads = Self::default();
+		for event in events {
+			gamepads.push(event);
+		}
+		gamepads
+	}
+
+	/// Connects a gamepad called `name`.
+	pub fn connect(&self, name: &str) -> GamepadId {
+		let gamepad = GamepadId(self.state().next_id);
+		self.push(GamepadEvent::Connected {
+			gamepad,
+			name: name.to_owned(),
+		});
+		gamepad
+	}
+
+	pub fn disconnect(&self, gamepad: GamepadId) {
+		self.push(GamepadEvent::Disconnected { gamepad });
+	}
+
+	pub fn press(&self, gamepad: GamepadId, button: GamepadButton) {
+		self.push(GamepadEvent::Button {
+			gamepad,
+			button,
+			pressed: true,
+		});
+	}
+
+	pub fn release(&self, gamepad: GamepadId, button: GamepadButton) {
+		self.push(GamepadEvent::Button {
+			gamepad,
+			button,
+			pressed: false,
+		});
+	}
+
+	pub fn set_axis(&self, gamepad: GamepadId, axis: GamepadAxis, value: f32) {
+		self.push(GamepadEvent::Axis {
+			gamepad,
+			axis,
+			value,
+		});
+	}
+
+	/// Queues `event` as if the gamepad had reported it.
+	pub fn push(&self, event: GamepadEvent) {
+		let mut state = self.state();
+		match &event {
+			GamepadEvent::Connected { gamepad, .. } => {
+				state.connected.insert(*gamepad);
+				state.next_id = state.next_id.max(gamepad.0 + 1);
+			},
+			GamepadEvent::Disconnected { gamepad } => {
+				state.connected.remove(gamepad);
+			},
+			_ => (),
+		}
+		state.events.push_back(event);
+	}
+
+	/// Every rumble started so far, oldest first.
+	pub fn rumbles(&self) -> Vec<(GamepadId, Rumble)> {
+		self.state().rumbles.clone()
+	}
+
+	fn state(&self) -> std::sync::MutexGuard<'_, FakeState> {
+		// The state stays consistent even if a holder of the lock panicked.
+		self.state.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+impl GamepadBackend for FakeGamepads {
+	fn poll(&mut self) -> Vec<GamepadEvent> {
+		self.state().events.drain(..).collect()
+	}
+
+	fn rumble(&mut self, gamepad: GamepadId, rumble: Rumble) -> Result<(), GamepadError> {
+		let mut state = self.state();
+		if !state.connected.contains(&gamepad) {
+			return Err(GamepadError::NotConnected(gamepad));
+		}
+		state.rumbles.push((gamepad, rumble));
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::input::{Deadzones, Input, InputEvent};
+
+	fn input_with(gamepads: &FakeGamepads) -> Input {
+		let mut input = Input::default();
+		input.set_gamepad_backend(Some(Box::new(gamepads.clone())));
+		input
+	}
+
+	fn poll(input: &mut Input) {
+		for event in input.poll_gamepad_backend() {
+			input.handle(&InputEvent::Gamepad(event));
+		}
+	}
+
+	#[test]
+	fn gamepads_connect_and_disconnect_between_polls() {
+		let gamepads = FakeGamepads::default();
+		let mut input = input_with(&gamepads);
+		let first = gamepads.connect("First");
+		let second = gamepads.connect("Second");
+		poll(&mut input);
+		assert_eq!(input.just_connected(), [first, second]);
+		assert_eq!(input.gamepad(second).unwrap().name(), "Second");
+
+		input.end_tick();
+		gamepads.press(first, GamepadButton::South);
+		gamepads.disconnect(first);
+		poll(&mut input);
+		assert_eq!(input.just_disconnected(), [first]);
+		assert!(input.gamepad(first).is_none());
+		assert_eq!(input.gamepads().count(), 1);
+		assert!(matches!(
+			input.rumble(
+				first,
+				Rumble {
+					strong: 1.0,
+					weak: 0.0,
+					duration: std::time::Duration::from_millis(100),
+				},
+			),
+			Err(GamepadError::NotConnected(_))
+		));
+
+		// Reconnecting gets a new id.
+		let third = gamepads.connect("First");
+		poll(&mut input);
+		assert_ne!(third, first);
+		assert_eq!(input.gamepads().count(), 2);
+	}
+
+	#[test]
+	fn replacing_the_backend_disconnects_its_gamepads() {
+		let gamepads = FakeGamepads::default();
+		let mut input = input_with(&gamepads);
+		let gamepad = gamepads.connect("Pad");
+		poll(&mut input);
+		input.set_gamepad_backend(None);
+		assert_eq!(input.just_disconnected(), [gamepad]);
+		assert_eq!(input.gamepads().count(), 0);
+	}
+
+	#[test]
+	fn sticks_apply_a_radial_deadzone() {
+		let gamepads = FakeGamepads::default();
+		let mut input = input_with(&gamepads);
+		input.set_deadzones(Deadzones {
+			stick: 0.2,
+			trigger: 0.1,
+		});
+		let gamepad = gamepads.connect("Pad");
+		gamepads.set_axis(gamepad, GamepadAxis::LeftX, 0.1);
+		gamepads.set_axis(gamepad, GamepadAxis::LeftY, 0.1);
+		gamepads.set_axis(gamepad, GamepadAxis::RightX, 0.6);
+		poll(&mut input);
+		let state = input.gamepad(gamepad).unwrap();
+		assert_eq!(state.left_stick(), shader::glam::Vec2::ZERO);
+		assert!((state.axis(GamepadAxis::RightX) - 0.5).abs() < 1e-6);
+		assert_eq!(state.axis(GamepadAxis::RightY), 0.0);
+
+		// Beyond the edge of the range, the stick is clamped to a unit length.
+		gamepads.set_axis(gamepad, GamepadAxis::LeftX, 1.0);
+		gamepads.set_axis(gamepad, GamepadAxis::LeftY, 1.0);
+		poll(&mut input);
+		let length = input.gamepad(gamepad).unwrap().left_stick().length();
+		assert!((length - 1.0).abs() < 1e-6);
+	}
+
+	#[test]
+	fn triggers_apply_their_deadzone_and_press_past_halfway() {
+		let gamepads = FakeGamepads::default();
+		let mut input = input_with(&gamepads);
+		let gamepad = gamepads.connect("Pad");
+		gamepads.set_axis(gamepad, GamepadAxis::LeftTrigger, 0.04);
+		gamepads.set_axis(gamepad, GamepadAxis::RightTrigger, 0.8);
+		poll(&mut input);
+		let state = input.gamepad(gamepad).unwrap();
+		assert_eq!(state.axis(GamepadAxis::LeftTrigger), 0.0);
+		assert!(!state.pressed(GamepadButton::LeftTrigger));
+		assert!(state.axis(GamepadAxis::RightTrigger) > 0.75);
+		assert!(state.pressed(GamepadButton::RightTrigger));
+	}
+}
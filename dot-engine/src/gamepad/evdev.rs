@@ -0,0 +1,382 @@
+//! Gamepads read from the evdev devices in `/dev/input`, which needs read and write access to
+//! them, usually by being in the `input` group. Devices the user can't open are skipped.
+//!
+//! A background thread watches `/dev/input` with inotify, rescanning the devices whenever one
+//! appears or udev changes its permissions, and starts a thread per gamepad found, which blocks
+//! reading its events until it's unplugged. Without inotify, it rescans every second. Rumble goes
+//! through a second handle to the device kept by the backend, so it doesn't wait for the reader.
+//!
+//! Dropping the backend stops the scanning thread within [`STOP_CHECK_INTERVAL`]. Reader threads
+//! stop on their gamepad's next event or when it's unplugged, as they can't be woken otherwise.
+
+use super::{GamepadAxis, GamepadBackend, GamepadButton, GamepadError, GamepadEvent, GamepadId};
+use evdev::{AbsoluteAxisType, InputEventKind, Key};
+
+const DEVICE_DIR: &str = "/dev/input";
+/// How often devices are rescanned without inotify.
+const RESCAN_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often the scanning thread checks for changes and whether the backend was dropped.
+const STOP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+enum Message {
+	Connected {
+		gamepad: GamepadId,
+		name: String,
+		/// `None` if the device can't rumble.
+		rumble: Option<Box<evdev::Device>>,
+	},
+	Event(GamepadEvent),
+}
+
+struct RumbleDevice {
+	device: evdev::Device,
+	/// The effect playing. Dropping it removes it from the device.
+	effect: Option<evdev::FFEffect>,
+}
+
+pub struct EvdevBackend {
+	receiver: std::sync::mpsc::Receiver<Message>,
+	connected: std::collections::HashMap<GamepadId, Option<RumbleDevice>>,
+	/// Cleared on drop to stop the threads.
+	running: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl EvdevBackend {
+	/// Starts looking for gamepads. Those already plugged in connect on one of the first polls.
+	pub fn spawn() -> Self {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		let running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+		let scan_running = running.clone();
+		std::thread::spawn(move || scan(sender, scan_running));
+		Self {
+			receiver,
+			connected: Default::default(),
+			running,
+		}
+	}
+}
+
+impl Drop for EvdevBackend {
+	fn drop(&mut self) {
+		self.running
+			.store(false, std::sync::atomic::Ordering::Relaxed);
+	}
+}
+
+impl GamepadBackend for EvdevBackend {
+	fn poll(&mut self) -> Vec<GamepadEvent> {
+		let mut events = Vec::new();
+		for message in self.receiver.try_iter() {
+			match message {
+				Message::Connected {
+					gamepad,
+					name,
+					rumble,
+				} => {
+					self.connected.insert(
+						gamepad,
+						rumble.map(|device| RumbleDevice {
+							device: *device,
+							effect: None,
+						}),
+					);
+					events.push(GamepadEvent::Connected { gamepad, name });
+				},
+				Message::Event(event) => {
+					if let GamepadEvent::Disconnected { gamepad } = event {
+						self.connected.remove(&gamepad);
+					}
+					events.push(event);
+				},
+			}
+		}
+		events
+	}
+
+	fn rumble(&mut self, gamepad: GamepadId, rumble: super::Rumble) -> Result<(), GamepadError> {
+		let device = self
+			.connected
+			.get_mut(&gamepad)
+			.ok_or(GamepadError::NotConnected(gamepad))?
+			.as_mut()
+			.ok_or(GamepadError::RumbleNotSupported(gamepad))?;
+		let magnitude = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+		let mut effect = device.device.upload_ff_effect(evdev::FFEffectData {
+			direction: 0,
+			trigger: evdev::FFTrigger {
+				button: 0,
+				interval: 0,
+			},
+			replay: evdev::FFReplay {
+				length: rumble.duration.as_millis().min(u16::MAX.into()) as u16,
+				delay: 0,
+			},
+			kind: evdev::FFEffectKind::Rumble {
+				strong_magnitude: magnitude(rumble.strong),
+				weak_magnitude: magnitude(rumble.weak),
+			},
+		})?;
+		effect.play(1)?;
+		device.effect = Some(effect);
+		Ok(())
+	}
+}
+
+/// Connects gamepads as they appear, until the backend is dropped.
+fn scan(
+	sender: std::sync::mpsc::Sender<Message>,
+	running: std::sync::Arc<std::sync::atomic::AtomicBool>,
+) {
+	let mut inotify = inotify::Inotify::init()
+		.and_then(|inotify| {
+			inotify.watches().add(
+				DEVICE_DIR,
+				inotify::WatchMask::CREATE | inotify::WatchMask::ATTRIB,
+			)?;
+			Ok(inotify)
+		})
+		.map_err(|e| {
+			println!("failed to watch {DEVICE_DIR}, rescanning gamepads every second: {e}")
+		})
+		.ok();
+	let mut buffer = [0; 4096];
+	let mut last_scan = None::<std::time::Instant>;
+
+	let open_paths = std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashSet::new()));
+	let mut next_id = 0;
+	while running.load(std::sync::atomic::Ordering::Relaxed) {
+		let changed = match &mut inotify {
+			Some(inotify) => {
+				// Drains everything queued; the watch is non-blocking.
+				let mut changed = false;
+				while let Ok(mut events) = inotify.read_events(&mut buffer) {
+					changed |= events.next().is_some();
+				}
+				changed
+			},
+			None => !last_scan.is_some_and(|last_scan| last_scan.elapsed() < RESCAN_INTERVAL),
+		};
+		if !changed && last_scan.is_some() {
+			std::thread::sleep(STOP_CHECK_INTERVAL);
+			continue;
+		}
+		last_scan = Some(std::time::Instant::now());
+
+		for (path, device) in evdev::enumerate() {
+			let is_gamepad = device
+				.supported_keys()
+				.is_some_and(|keys| keys.contains(Key::BTN_SOUTH));
+			if !is_gamepad || !open_paths.lock().unwrap().insert(path.clone()) {
+				continue;
+			}
+			let gamepad = GamepadId(next_id);
+			next_id += 1;
+			let can_rumble = device
+				.supported_ff()
+				.is_some_and(|effects| effects.contains(evdev::FFEffectType::FF_RUMBLE));
+			let connected = Message::Connected {
+				gamepad,
+				name: device.name().unwrap_or("Gamepad").to_owned(),
+				rumble: if can_rumble {
+					evdev::Device::open(&path).ok().map(Box::new)
+				} else {
+					None
+				},
+			};
+			if sender.send(connected).is_err() {
+				return;
+			}
+
+			let sender = sender.clone();
+			let open_paths = open_paths.clone();
+			let running = running.clone();
+			std::thread::spawn(move || {
+				read(gamepad, device, &sender, &running);
+				open_paths.lock().unwrap().remove(&path);
+				let _ = sender.send(Message::Event(GamepadEvent::Disconnected { gamepad }));
+			});
+		}
+	}
+}
+
+/// Sends the events of `device` until reading fails, which it does once it's unplugged, or the
+/// backend is dropped.
+fn read(
+	gamepad: GamepadId,
+	mut device: evdev::Device,
+	sender: &std::sync::mpsc::Sender<Message>,
+	running: &std::sync::atomic::AtomicBool,
+) {
+	let Ok(abs_state) = device.get_abs_state() else {
+		return;
+	};
+	// The range of each axis, and its initial value, which isn't reported as an event.
+	let mut ranges = std::collections::HashMap::new();
+	for axis in device
+		.supported_absolute_axes()
+		.into_iter()
+		.flat_map(|axes| axes.iter())
+	{
+		let info = &abs_state[axis.0 as usize];
+		ranges.insert(axis.0, (info.minimum, info.maximum));
+		for event in axis_events(gamepad, axis, info.value, (info.minimum, info.maximum)) {
+			if sender.send(Message::Event(event)).is_err() {
+				return;
+			}
+		}
+	}
+
+	while running.load(std::sync::atomic::Ordering::Relaxed) {
+		let Ok(events) = device.fetch_events() else {
+			return;
+		};
+		for event in events {
+			let events = match event.kind() {
+				InputEventKind::Key(key) => button(key)
+					.map(|button| GamepadEvent::Button {
+						gamepad,
+						button,
+						pressed: event.value() != 0,
+					})
+					.into_iter()
+					.collect(),
+				InputEventKind::AbsAxis(axis) => match ranges.get(&axis.0) {
+					Some(&range) => axis_events(gamepad, axis, event.value(), range),
+					None => Vec::new(),
+				},
+				_ => Vec::new(),
+			};
+			for event in events {
+				if sender.send(Message::Event(event)).is_err() {
+					return;
+				}
+			}
+		}
+	}
+}
+
+fn button(key: Key) -> Option<GamepadButton> {
+	Some(match key {
+		Key::BTN_SOUTH => GamepadButton::South,
+		Key::BTN_EAST => GamepadButton::East,
+		Key::BTN_NORTH => GamepadButton::North,
+		Key::BTN_WEST => GamepadButton::West,
+		Key::BTN_TL => GamepadButton::LeftShoulder,
+		Key::BTN_TR => GamepadButton::RightShoulder,
+		Key::BTN_TL2 => GamepadButton::LeftTrigger,
+		Key::BTN_TR2 => GamepadButton::RightTrigger,
+		Key::BTN_SELECT => GamepadButton::Select,
+		Key::BTN_START => GamepadButton::Start,
+		Key::BTN_MODE => GamepadButton::Mode,
+		Key::BTN_THUMBL => GamepadButton::LeftStick,
+		Key::BTN_THUMBR => GamepadButton::RightStick,
+		Key::BTN_DPAD_UP => GamepadButton::DPadUp,
+		Key::BTN_DPAD_DOWN => GamepadButton::DPadDown,
+		Key::BTN_DPAD_LEFT => GamepadButton::DPadLeft,
+		Key::BTN_DPAD_RIGHT => GamepadButton::DPadRight,
+		_ => return None,
+	})
+}
+
+/// Events for `axis` having the raw `value` in `range`. Most gamepads report the d-pad as a hat,
+/// an axis from -1 to 1 per direction, which becomes presses and releases of the d-pad buttons.
+fn axis_events(
+	gamepad: GamepadId,
+	axis: AbsoluteAxisType,
+	value: i32,
+	(minimum, maximum): (i32, i32),
+) -> Vec<GamepadEvent> {
+	let hat = |negative, positive| {
+		vec![
+			GamepadEvent::Button {
+				gamepad,
+				button: negative,
+				pressed: value < 0,
+			},
+			GamepadEvent::Button {
+				gamepad,
+				button: positive,
+				pressed: value > 0,
+			},
+		]
+	};
+	let axis = match axis {
+		AbsoluteAxisType::ABS_X => GamepadAxis::LeftX,
+		AbsoluteAxisType::ABS_Y => GamepadAxis::LeftY,
+		AbsoluteAxisType::ABS_RX => GamepadAxis::RightX,
+		AbsoluteAxisType::ABS_RY => GamepadAxis::RightY,
+		AbsoluteAxisType::ABS_Z => GamepadAxis::LeftTrigger,
+		AbsoluteAxisType::ABS_RZ => GamepadAxis::RightTrigger,
+		AbsoluteAxisType::ABS_HAT0X => {
+			return hat(GamepadButton::DPadLeft, GamepadButton::DPadRight)
+		},
+		AbsoluteAxisType::ABS_HAT0Y => return hat(GamepadButton::DPadUp, GamepadButton::DPadDown),
+		_ => return Vec::new(),
+	};
+	if maximum <= minimum {
+		return Vec::new();
+	}
+	// In i64, as the range of a 32-bit axis overflows i32.
+	let fraction = ((value as i64 - minimum as i64) as f64
+		/ (maximum as i64 - minimum as i64) as f64)
+		.clamp(0.0, 1.0) as f32;
+	let value = if axis.is_trigger() {
+		fraction
+	} else {
+		fraction * 2.0 - 1.0
+	};
+	vec![GamepadEvent::Axis {
+		gamepad,
+		axis,
+		value,
+	}]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn axis_value(axis: AbsoluteAxisType, value: i32, range: (i32, i32)) -> f32 {
+		match axis_events(GamepadId(0), axis, value, range)[..] {
+			[GamepadEvent::Axis { value, .. }] => value,
+			ref events => panic!("expected one axis event, got {events:?}"),
+		}
+	}
+
+	#[test]
+	fn axes_are_normalized_over_their_range() {
+		let range = (-32768, 32767);
+		assert_eq!(axis_value(AbsoluteAxisType::ABS_X, -32768, range), -1.0);
+		assert_eq!(axis_value(AbsoluteAxisType::ABS_X, 32767, range), 1.0);
+		assert_eq!(axis_value(AbsoluteAxisType::ABS_Z, 0, (0, 255)), 0.0);
+		assert_eq!(axis_value(AbsoluteAxisType::ABS_Z, 255, (0, 255)), 1.0);
+	}
+
+	#[test]
+	fn full_32_bit_ranges_do_not_overflow() {
+		let range = (i32::MIN, i32::MAX);
+		assert_eq!(axis_value(AbsoluteAxisType::ABS_X, i32::MIN, range), -1.0);
+		assert_eq!(axis_value(AbsoluteAxisType::ABS_X, i32::MAX, range), 1.0);
+		assert!(axis_value(AbsoluteAxisType::ABS_X, 0, range).abs() < 1e-6);
+	}
+
+	#[test]
+	fn hats_become_dpad_buttons() {
+		let events = axis_events(GamepadId(0), AbsoluteAxisType::ABS_HAT0X, -1, (-1, 1));
+		assert_eq!(
+			events,
+			[
+				GamepadEvent::Button {
+					gamepad: GamepadId(0),
+					button: GamepadButton::DPadLeft,
+					pressed: true,
+				},
+				GamepadEvent::Button {
+					gamepad: GamepadId(0),
+					button: GamepadButton::DPadRight,
+					pressed: false,
+				},
+			]
+		);
+	}
+}
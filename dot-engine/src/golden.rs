@@ -0,0 +1,278 @@
+//! Golden-image regression tests for shader entry points, run with `dot --golden`.
+//!
+//! Each case is rendered headlessly, read back and compared against
+//! `shader/golden/<entry point>.png`, and against the kernel run on the CPU if the case has one.
+//! On failure the actual image and a diff image (mismatching pixels in red) are written to
+//! `target/golden/`. `--bless` overwrites the references instead. Set `DOT_DEVICE` to pick the
+//! device, e.g. `DOT_DEVICE=llvmpipe` for lavapipe.
+
+use crate::screenshot::Frame;
+
+const REFERENCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shader/golden");
+const OUTPUT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/golden");
+
+pub struct GoldenCase {
+	pub entry_point: crate::shaders::EntryPoint,
+	pub extent: [u32; 2],
+	/// Largest allowed difference in any channel of any pixel.
+	pub tolerance: u8,
+	/// The entry point's body, run through [`shader::cpu::dispatch`] with the entry point's
+	/// workgroup size.
+	pub cpu_kernel: Option<fn(&shader::FrameParams, shader::cpu::Invocation, &shader::cpu::Image)>,
+}
+
+pub const CASES: &[GoldenCase] = &[GoldenCase {
+	entry_point: crate::shaders::EntryPoint::Main,
+	extent: [64, 64],
+	tolerance: 1,
+	cpu_kernel: Some(|params, invocation, image| {
+		shader::gradient(params, invocation.global_invocation_id, image)
+	}),
+}];
+
+pub struct Options {
+	/// Overrides the tolerance of every case.
+	pub tolerance: Option<u8>,
+	/// Writes the rendered images as the new references instead of comparing.
+	pub bless: bool,
+}
+
+pub struct Comparison {
+	pub mismatched_pixels: usize,
+	pub max_difference: u8,
+	pub diff: Frame,
+}
+
+/// Compares two frames channel by channel. Fails if their sizes differ.
+pub fn compare(actual: &Frame, expected: &Frame, tolerance: u8) -> Result<Comparison, String> {
+	if [actual.width, actual.height] != [expected.width, expected.height] {
+		return Err(format!(
+			"rendered {}x{} but the reference is {}x{}",
+			actual.width, actual.height, expected.width, expected.height,
+		));
+	}
+	let mut mismatched_pixels = 0;
+	let mut max_difference = 0;
+	let mut diff = Vec::with_capacity(expected.pixels.len());
+	for (actual, expected) in actual
+		.pixels
+		.chunks_exact(4)
+		.zip(expected.pixels.chunks_exact(4))
+	{
+		let difference = actual
+			.iter()
+			.zip(expected)
+			.map(|(a, e)| a.abs_diff(*e))
+			.max()
+			.unwrap_or(0);
+		max_difference = max_difference.max(difference);
+		if difference > tolerance {
+			mismatched_pixels += 1;
+			diff.extend_from_slice(&[255, 0, 0, 255]);
+		} else {
+			let luma =
+				((expected[0] as u32 * 3 + expected[1] as u32 * 6 + expected[2] as u32) / 40) as u8;
+			diff.extend_from_slice(&[luma, luma, luma, 255]);
+		}
+	}
+	Ok(Comparison {
+		mismatched_pixels,
+		max_difference,
+		diff: Frame {
+			width: expected.width,
+			height: expected.height,
+			pixels: diff,
+		},
+	})
+}
+
+/// Runs every case in [`CASES`], printing a line per case. Returns whether all passed.
+pub fn run(shaders: &crate::reflect::Modules, options: &Options) -> bool {
+	let device_selection = match crate::DeviceSelection::from_env() {
+		Ok(device_selection) => device_selection,
+		Err(e) => {
+			println!("golden: {e}");
+			return false;
+		},
+	};
+	let mut passed = true;
+	for case in CASES {
+		let tolerance = options.tolerance.unwrap_or(case.tolerance);
+		let result = run_case(shaders, case, tolerance, options.bless, &device_selection);
+		match &result {
+			Ok(()) => println!("golden {}: ok", case.entry_point),
+			Err(message) => println!("golden {}: FAILED: {message}", case.entry_point),
+		}
+		passed &= result.is_ok();
+	}
+	passed
+}
+
+fn run_case(
+	shaders: &crate::reflect::Modules,
+	case: &GoldenCase,
+	tolerance: u8,
+	bless: bool,
+	device_selection: &crate::DeviceSelection,
+) -> Result<(), String> {
+	let mut renderer = crate::Renderer::new_headless(
+		case.extent,
+		vulkano::Version::major_minor(0, 1),
+		shaders,
+		&crate::graph::RenderGraph::single(case.entry_point),
+		device_selection,
+	)
+	.map_err(|e| crate::report(&e))?;
+	let actual = renderer
+		.capture_offscreen()
+		.map_err(|e| crate::report(&e))?;
+
+	let reference_path =
+		std::path::Path::new(REFERENCE_DIR).join(format!("{}.png", case.entry_point));
+	if bless {
+		std::fs::create_dir_all(REFERENCE_DIR).map_err(|e| e.to_string())?;
+		actual.save(&reference_path).map_err(|e| e.to_string())?;
+		println!(
+			"golden {}: blessed {}",
+			case.entry_point,
+			reference_path.display()
+		);
+		return Ok(());
+	}
+
+	let expected = Frame::load_png(&reference_path).map_err(|e| {
+		format!(
+			"cannot load {}: {e} (run with --bless to create it)",
+			reference_path.display()
+		)
+	})?;
+	let failure = if let Some(failure) = compare_with_cpu(shaders, case, &actual, tolerance)? {
+		failure
+	} else {
+		let comparison = compare(&actual, &expected, tolerance)?;
+		if comparison.mismatched_pixels == 0 {
+			return Ok(());
+		}
+		let diff_path =
+			std::path::Path::new(OUTPUT_DIR).join(format!("{}.diff.png", case.entry_point));
+		std::fs::create_dir_all(OUTPUT_DIR).map_err(|e| e.to_string())?;
+		comparison
+			.diff
+			.save(&diff_path)
+			.map_err(|e| e.to_string())?;
+		format!(
+			"{} pixels differ by more than {tolerance} (max difference {}), diff written to {}",
+			comparison.mismatched_pixels,
+			comparison.max_difference,
+			diff_path.display(),
+		)
+	};
+
+	let actual_path =
+		std::path::Path::new(OUTPUT_DIR).join(format!("{}.actual.png", case.entry_point));
+	std::fs::create_dir_all(OUTPUT_DIR).map_err(|e| e.to_string())?;
+	actual.save(actual_path).map_err(|e| e.to_string())?;
+	Err(failure)
+}
+
+/// Runs the case's CPU kernel, if any, and compares the GPU frame against it. Returns a failure
+/// message on mismatch, after writing the CPU image and the diff to [`OUTPUT_DIR`].
+fn compare_with_cpu(
+	shaders: &crate::reflect::Modules,
+	case: &GoldenCase,
+	actual: &Frame,
+	tolerance: u8,
+) -> Result<Option<String>, String> {
+	let Some(cpu_kernel) = case.cpu_kernel else {
+		return Ok(None);
+	};
+	let local_size = shaders
+		.find(case.entry_point.name())
+		.and_then(|spirv| crate::reflect::local_size(spirv, case.entry_point.name()))
+		.unwrap_or([1, 1, 1]);
+
+	let image = shader::cpu::Image::new(case.extent[0], case.extent[1]);
+	let params = shader::FrameParams {
+		resolution: case.extent.into(),
+		..Default::default()
+	};
+	shader::cpu::dispatch(
+		crate::workgroup_count(case.extent, local_size).into(),
+		local_size.into(),
+		|invocation| cpu_kernel(&params, invocation, &image),
+	);
+	let expected = Frame {
+		width: image.width(),
+		height: image.height(),
+		pixels: image.to_rgba8(),
+	};
+
+	let comparison = compare(actual, &expected, tolerance)?;
+	if comparison.mismatched_pixels == 0 {
+		return Ok(None);
+	}
+	let cpu_path = std::path::Path::new(OUTPUT_DIR).join(format!("{}.cpu.png", case.entry_point));
+	let diff_path =
+		std::path::Path::new(OUTPUT_DIR).join(format!("{}.cpu-diff.png", case.entry_point));
+	std::fs::create_dir_all(OUTPUT_DIR).map_err(|e| e.to_string())?;
+	expected.save(cpu_path).map_err(|e| e.to_string())?;
+	comparison
+		.diff
+		.save(&diff_path)
+		.map_err(|e| e.to_string())?;
+	Ok(Some(format!(
+		"{} pixels differ from the CPU kernel by more than {tolerance} (max difference {}), diff \
+		 written to {}",
+		comparison.mismatched_pixels,
+		comparison.max_difference,
+		diff_path.display(),
+	)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn frame(width: u32, height: u32, pixels: &[[u8; 4]]) -> Frame {
+		Frame {
+			width,
+			height,
+			pixels: pixels.concat(),
+		}
+	}
+
+	#[test]
+	fn allows_differences_up_to_the_tolerance() {
+		let expected = frame(2, 1, &[[100, 200, 40, 255], [0, 0, 0, 255]]);
+		let actual = frame(2, 1, &[[102, 200, 40, 255], [0, 0, 3, 255]]);
+
+		let comparison = compare(&actual, &expected, 3).unwrap();
+		assert_eq!(comparison.mismatched_pixels, 0);
+		assert_eq!(comparison.max_difference, 3);
+
+		let comparison = compare(&actual, &expected, 2).unwrap();
+		assert_eq!(comparison.mismatched_pixels, 1);
+		assert_eq!(comparison.max_difference, 3);
+	}
+
+	#[test]
+	fn marks_mismatches_red_over_dimmed_luma() {
+		let expected = frame(2, 1, &[[100, 200, 40, 255], [0, 0, 0, 255]]);
+		let actual = frame(2, 1, &[[100, 200, 40, 0], [0, 0, 0, 255]]);
+		let comparison = compare(&actual, &expected, 0).unwrap();
+		assert_eq!(comparison.mismatched_pixels, 1);
+		assert_eq!(comparison.max_difference, 255);
+		assert_eq!([comparison.diff.width, comparison.diff.height], [2, 1]);
+		assert_eq!(comparison.diff.pixels, [255, 0, 0, 255, 0, 0, 0, 255]);
+
+		let comparison = compare(&expected, &expected, 0).unwrap();
+		assert_eq!(comparison.diff.pixels, [38, 38, 38, 255, 0, 0, 0, 255]);
+	}
+
+	#[test]
+	fn rejects_frames_of_different_sizes() {
+		let expected = frame(2, 1, &[[0, 0, 0, 255]; 2]);
+		assert!(compare(&frame(1, 1, &[[0, 0, 0, 255]]), &expected, 0).is_err());
+		assert!(compare(&frame(1, 2, &[[0, 0, 0, 255]; 2]), &expected, 0).is_err());
+	}
+}
@@ -0,0 +1,451 @@
+//! Frames as a sequence of named compute passes.
+//!
+//! A [`RenderGraph`] lists passes in execution order. Each pass dispatches one shader entry point
+//! over the whole frame with its images and buffers bound to descriptor set 0, plus the frame
+//! parameters at binding 1 if the shader declares it. Images are either the render target or
+//! transient images, which the renderer allocates at the frame's size on first use and keeps
+//! until the size changes. All passes of a frame are recorded into one command buffer, where
+//! vulkano inserts the pipeline barriers between a pass writing an image and a later pass reading
+//! it. Extracted buffers are uploaded from the frame's [`crate::ecs::Extract`] as it is recorded.
+
+use crate::{
+	reflect::{DescriptorBinding, DescriptorKind},
+	RendererError,
+};
+use vulkano::pipeline::Pipeline;
+
+/// An image a pass binds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageRef {
+	/// The swapchain image or offscreen image being rendered.
+	Target,
+	/// An intermediate image in `OFFSCREEN_FORMAT`, shared by every pass naming it.
+	Transient(String),
+}
+
+#[derive(Clone)]
+pub struct Pass {
+	/// Shown in errors.
+	pub name: String,
+	/// Looked up by name in the renderer's modules, which may come from a `--shader` file.
+	pub entry_point: crate::shaders::EntryPoint,
+	/// Storage images the pass reads, by binding. Transient images must be written by an
+	/// earlier pass.
+	pub inputs: Vec<(u32, ImageRef)>,
+	/// Storage images the pass writes, by binding.
+	pub outputs: Vec<(u32, ImageRef)>,
+	/// Storage buffers, by binding.
+	pub buffers: Vec<(u32, vulkano::buffer::Subbuffer<[u8]>)>,
+	/// Storage buffers filled each frame from the [`crate::ecs::Extract`] buffer of the given
+	/// name, by binding.
+	pub extracted_buffers: Vec<(u32, String)>,
+}
+
+#[derive(Clone, Default)]
+pub struct RenderGraph {
+	pub passes: Vec<Pass>,
+}
+
+impl RenderGraph {
+	/// A graph of one pass running `entry_point` and writing the target at binding 0.
+	pub fn single(entry_point: crate::shaders::EntryPoint) -> Self {
+		Self {
+			passes: vec![Pass {
+				outputs: vec![(0, ImageRef::Target)],
+				..Pass::new(entry_point)
+			}],
+		}
+	}
+}
+
+impl Pass {
+	/// A pass running `entry_point` and named after it, without any bindings.
+	pub fn new(entry_point: crate::shaders::EntryPoint) -> Self {
+		Self {
+			name: entry_point.name().to_owned(),
+			entry_point,
+			inputs: Vec::new(),
+			outputs: Vec::new(),
+			buffers: Vec::new(),
+			extracted_buffers: Vec::new(),
+		}
+	}
+
+	/// Names of the transient images the pass reads or writes.
+	pub fn transient_images(&self) -> impl Iterator<Item = &str> {
+		self.inputs
+			.iter()
+			.chain(&self.outputs)
+			.filter_map(|(_, image)| match image {
+				ImageRef::Target => None,
+				ImageRef::Transient(name) => Some(name.as_str()),
+			})
+	}
+}
+
+/// A pass with its pipeline.
+pub struct CompiledPass {
+	pub pass: Pass,
+	pub pipeline: std::sync::Arc<vulkano::pipeline::ComputePipeline>,
+	/// Workgroup size of the entry point, reflected from the SPIR-V.
+	pub local_size: [u32; 3],
+}
+
+/// Creates the pipeline of every pass in `graph` from the module in `modules` declaring its entry
+/// point. Checks that the modules' SPIR-V versions are supported, that transient images are
+/// written before they are read, and that what each pass binds matches the descriptor layout
+/// reflected from its entry point.
+pub fn compile(
+	device: &std::sync::Arc<vulkano::device::Device>,
+	modules: &crate::reflect::Modules,
+	graph: &RenderGraph,
+) -> Result<Vec<CompiledPass>, RendererError> {
+	check_transient_images(graph)?;
+
+	let supported_version = max_spirv_version(device.api_version());
+	let mut shader_modules: Vec<(&[u32], std::sync::Arc<vulkano::shader::ShaderModule>)> =
+		Vec::new();
+
+	let mut compiled = Vec::with_capacity(graph.passes.len());
+	for pass in &graph.passes {
+		let entry_point = pass.entry_point.name();
+		let spirv = modules
+			.find(entry_point)
+			.ok_or_else(|| RendererError::MissingEntryPoint(entry_point.to_owned()))?;
+		let module = match shader_modules
+			.iter()
+			.find(|(module_spirv, _)| std::ptr::eq(*module_spirv, spirv))
+		{
+			Some((_, module)) => module.clone(),
+			None => {
+				crate::reflect::check_version(spirv, supported_version)?;
+				let module = unsafe {
+					vulkano::shader::ShaderModule::new(
+						device.clone(),
+						vulkano::shader::ShaderModuleCreateInfo::new(spirv),
+					)
+				}
+				.map_err(RendererError::Pipeline)?;
+				shader_modules.push((spirv, module.clone()));
+				module
+			},
+		};
+
+		let pipeline = create_pipeline(device, &module, entry_point)?;
+		check_bindings(pass, &declared_bindings(&pipeline))?;
+
+		compiled.push(CompiledPass {
+			pass: pass.clone(),
+			pipeline,
+			local_size: crate::reflect::local_size(spirv, entry_point).unwrap_or([1, 1, 1]),
+		});
+	}
+	Ok(compiled)
+}
+
+/// The newest SPIR-V version, as (major, minor), that Vulkan `api_version` accepts.
+fn max_spirv_version(api_version: vulkano::Version) -> (u8, u8) {
+	if api_version >= vulkano::Version::V1_3 {
+		(1, 6)
+	} else if api_version >= vulkano::Version::V1_2 {
+		(1, 5)
+	} else if api_version >= vulkano::Version::V1_1 {
+		(1, 3)
+	} else {
+		(1, 0)
+	}
+}
+
+/// Checks that every transient image is written by a pass before a later pass reads it.
+fn check_transient_images(graph: &RenderGraph) -> Result<(), RendererError> {
+	let mut written = Vec::new();
+	for pass in &graph.passes {
+		for (_, image) in &pass.inputs {
+			if let ImageRef::Transient(name) = image {
+				if !written.contains(&name) {
+					return Err(RendererError::UnwrittenImage {
+						pass: pass.name.clone(),
+						image: name.clone(),
+					});
+				}
+			}
+		}
+		written.extend(pass.outputs.iter().filter_map(|(_, image)| match image {
+			ImageRef::Target => None,
+			ImageRef::Transient(name) => Some(name),
+		}));
+	}
+	Ok(())
+}
+
+/// The bindings of `pipeline`'s descriptor set 0, which vulkano reflects from just the bindings
+/// its entry point uses, even in a module with several entry points.
+fn declared_bindings(pipeline: &vulkano::pipeline::ComputePipeline) -> Vec<DescriptorBinding> {
+	use vulkano::descriptor_set::layout::DescriptorType;
+
+	let Some(layout) = pipeline.layout().set_layouts().get(0) else {
+		return Vec::new();
+	};
+	let mut bindings: Vec<_> = layout
+		.bindings()
+		.iter()
+		.map(|(&binding, declared)| DescriptorBinding {
+			set: 0,
+			binding,
+			kind: match declared.descriptor_type {
+				DescriptorType::Sampler => DescriptorKind::Sampler,
+				DescriptorType::CombinedImageSampler => DescriptorKind::CombinedImageSampler,
+				DescriptorType::SampledImage => DescriptorKind::SampledImage,
+				DescriptorType::StorageImage => DescriptorKind::StorageImage,
+				DescriptorType::UniformBuffer | DescriptorType::UniformBufferDynamic => {
+					DescriptorKind::UniformBuffer
+				},
+				DescriptorType::StorageBuffer | DescriptorType::StorageBufferDynamic => {
+					DescriptorKind::StorageBuffer
+				},
+				_ => DescriptorKind::Other,
+			},
+		})
+		.collect();
+	bindings.sort_by_key(|binding| binding.binding);
+	bindings
+}
+
+/// Compares what `pass` binds in set 0 against the bindings its shader declares, as
+/// [`crate::reflect::descriptor_bindings`] lists them. The frame parameters are bound at binding 1
+/// when the shader declares it and the pass leaves it free.
+fn check_bindings(pass: &Pass, declared: &[DescriptorBinding]) -> Result<(), RendererError> {
+	let bound: Vec<_> = pass
+		.inputs
+		.iter()
+		.chain(&pass.outputs)
+		.map(|(binding, _)| (*binding, DescriptorKind::StorageImage))
+		.chain(
+			pass.buffers
+				.iter()
+				.map(|(binding, _)| binding)
+				.chain(pass.extracted_buffers.iter().map(|(binding, _)| binding))
+				.map(|binding| (*binding, DescriptorKind::StorageBuffer)),
+		)
+		.collect();
+	let is_bound = |binding| bound.iter().any(|(bound, _)| *bound == binding);
+	let is_buffer = |kind| {
+		matches!(
+			kind,
+			DescriptorKind::StorageBuffer | DescriptorKind::UniformBuffer
+		)
+	};
+
+	let declared: Vec<_> = declared
+		.iter()
+		.filter(|declared| declared.set == 0)
+		.collect();
+	for &(binding, expected) in &bound {
+		let Some(declared) = declared.iter().find(|declared| declared.binding == binding) else {
+			return Err(RendererError::MissingBinding {
+				pass: pass.name.clone(),
+				binding,
+			});
+		};
+		let compatible = if expected == DescriptorKind::StorageBuffer {
+			is_buffer(declared.kind)
+		} else {
+			declared.kind == expected
+		};
+		if !compatible {
+			return Err(RendererError::BindingTypeMismatch {
+				pass: pass.name.clone(),
+				binding,
+				bound: expected,
+				declared: declared.kind,
+			});
+		}
+	}
+
+	for declared in declared {
+		if is_bound(declared.binding) {
+			continue;
+		}
+		if declared.binding != 1 {
+			return Err(RendererError::UnboundBinding {
+				pass: pass.name.clone(),
+				binding: declared.binding,
+			});
+		}
+		if !is_buffer(declared.kind) {
+			return Err(RendererError::BindingTypeMismatch {
+				pass: pass.name.clone(),
+				binding: declared.binding,
+				bound: DescriptorKind::StorageBuffer,
+				declared: declared.kind,
+			});
+		}
+	}
+	Ok(())
+}
+
+fn create_pipeline(
+	device: &std::sync::Arc<vulkano::device::Device>,
+	module: &std::sync::Arc<vulkano::shader::ShaderModule>,
+	entry_point: &str,
+) -> Result<std::sync::Arc<vulkano::pipeline::ComputePipeline>, RendererError> {
+	let shader = module
+		.entry_point(entry_point)
+		.ok_or_else(|| RendererError::MissingEntryPoint(entry_point.to_owned()))?;
+
+	let stage = vulkano::pipeline::PipelineShaderStageCreateInfo::new(shader);
+
+	let layout = vulkano::pipeline::PipelineLayout::new(
+		device.clone(),
+		vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+			.into_pipeline_layout_create_info(device.clone())
+			.map_err(RendererError::PipelineLayout)?,
+	)
+	.map_err(RendererError::Pipeline)?;
+
+	vulkano::pipeline::ComputePipeline::new(
+		device.clone(),
+		None,
+		vulkano::pipeline::compute::ComputePipelineCreateInfo::stage_layout(stage, layout),
+	)
+	.map_err(RendererError::Pipeline)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::shaders::EntryPoint;
+
+	fn transient(name: &str) -> ImageRef {
+		ImageRef::Transient(name.to_owned())
+	}
+
+	fn pass(inputs: Vec<(u32, ImageRef)>, outputs: Vec<(u32, ImageRef)>) -> Pass {
+		Pass {
+			inputs,
+			outputs,
+			..Pass::new(EntryPoint::Main)
+		}
+	}
+
+	fn declared(bindings: &[(u32, u32, DescriptorKind)]) -> Vec<DescriptorBinding> {
+		bindings
+			.iter()
+			.map(|&(set, binding, kind)| DescriptorBinding { set, binding, kind })
+			.collect()
+	}
+
+	#[test]
+	fn requires_transient_images_to_be_written_first() {
+		let graph = |passes| RenderGraph { passes };
+		assert!(check_transient_images(&graph(vec![
+			pass(vec![], vec![(0, transient("scene"))]),
+			pass(vec![(0, transient("scene"))], vec![(1, ImageRef::Target)]),
+		]))
+		.is_ok());
+
+		for passes in [
+			vec![
+				pass(vec![(0, transient("scene"))], vec![(1, ImageRef::Target)]),
+				pass(vec![], vec![(0, transient("scene"))]),
+			],
+			// A pass doesn't count as writing what it reads.
+			vec![pass(
+				vec![(0, transient("scene"))],
+				vec![(1, transient("scene"))],
+			)],
+			vec![
+				pass(vec![], vec![(0, transient("other"))]),
+				pass(vec![(0, transient("scene"))], vec![]),
+			],
+		] {
+			assert!(matches!(
+				check_transient_images(&graph(passes)),
+				Err(RendererError::UnwrittenImage { image, .. }) if image == "scene"
+			));
+		}
+	}
+
+	#[test]
+	fn accepts_matching_bindings() {
+		let single = &RenderGraph::single(EntryPoint::Main).passes[0];
+		assert!(check_bindings(single, EntryPoint::Main.bindings()).is_ok());
+
+		let declared = declared(&[
+			(0, 0, DescriptorKind::StorageImage),
+			(0, 1, DescriptorKind::UniformBuffer),
+			(0, 2, DescriptorKind::StorageImage),
+			(0, 3, DescriptorKind::StorageBuffer),
+			// Other sets aren't bound by passes.
+			(1, 0, DescriptorKind::StorageBuffer),
+		]);
+		let pass = Pass {
+			extracted_buffers: vec![(3, "sprites".to_owned())],
+			..pass(vec![(2, transient("scene"))], vec![(0, ImageRef::Target)])
+		};
+		assert!(check_bindings(&pass, &declared).is_ok());
+	}
+
+	#[test]
+	fn binds_frame_params_only_to_a_free_buffer_at_binding_1() {
+		let storage_image = declared(&[(0, 1, DescriptorKind::StorageImage)]);
+		let pass_binding_1 = pass(vec![], vec![(1, ImageRef::Target)]);
+		assert!(check_bindings(&pass_binding_1, &storage_image).is_ok());
+		assert!(matches!(
+			check_bindings(&pass(vec![], vec![]), &storage_image),
+			Err(RendererError::BindingTypeMismatch {
+				binding: 1,
+				bound: DescriptorKind::StorageBuffer,
+				declared: DescriptorKind::StorageImage,
+				..
+			})
+		));
+		for kind in [DescriptorKind::StorageBuffer, DescriptorKind::UniformBuffer] {
+			assert!(check_bindings(&pass(vec![], vec![]), &declared(&[(0, 1, kind)])).is_ok());
+		}
+	}
+
+	#[test]
+	fn rejects_mismatched_bindings() {
+		let declared = declared(&[
+			(0, 0, DescriptorKind::StorageImage),
+			(0, 1, DescriptorKind::StorageBuffer),
+		]);
+
+		let extra = pass(vec![(2, transient("scene"))], vec![(0, ImageRef::Target)]);
+		assert!(matches!(
+			check_bindings(&extra, &declared),
+			Err(RendererError::MissingBinding { binding: 2, .. })
+		));
+
+		assert!(matches!(
+			check_bindings(&pass(vec![], vec![]), &declared),
+			Err(RendererError::UnboundBinding { binding: 0, .. })
+		));
+
+		let buffer_at_image = Pass {
+			extracted_buffers: vec![(0, "sprites".to_owned())],
+			..pass(vec![], vec![])
+		};
+		assert!(matches!(
+			check_bindings(&buffer_at_image, &declared),
+			Err(RendererError::BindingTypeMismatch {
+				binding: 0,
+				bound: DescriptorKind::StorageBuffer,
+				declared: DescriptorKind::StorageImage,
+				..
+			})
+		));
+
+		let image_at_buffer = pass(vec![(1, transient("scene"))], vec![(0, ImageRef::Target)]);
+		assert!(matches!(
+			check_bindings(&image_at_buffer, &declared),
+			Err(RendererError::BindingTypeMismatch {
+				binding: 1,
+				bound: DescriptorKind::StorageImage,
+				declared: DescriptorKind::StorageBuffer,
+				..
+			})
+		));
+	}
+}
@@ -0,0 +1,81 @@
+//! Rebuilds the shader crate in the background when its sources change, enabled with the
+//! `hot-reload` feature.
+//!
+//! The sources are polled for modification times rather than watched through the OS, which is
+//! plenty for a handful of files. Build diagnostics go to stderr as with any cargo build.
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shader");
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Handle to the background thread watching the shader crate.
+pub struct ShaderWatcher {
+	receiver: std::sync::mpsc::Receiver<Result<crate::reflect::Modules, String>>,
+}
+
+impl ShaderWatcher {
+	/// Starts watching. The first build happens on the first change; until then the renderer
+	/// keeps the SPIR-V embedded at compile time.
+	pub fn spawn() -> Self {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		std::thread::spawn(move || {
+			let mut last_modified = latest_modification();
+			loop {
+				std::thread::sleep(POLL_INTERVAL);
+				let modified = latest_modification();
+				if modified <= last_modified {
+					continue;
+				}
+				last_modified = modified;
+
+				println!("Shader sources changed, rebuilding");
+				if sender.send(build()).is_err() {
+					return;
+				}
+			}
+		});
+		Self { receiver }
+	}
+
+	/// Returns the result of the most recent rebuild finished since the last call, if any: the
+	/// new modules, or a message describing why the build failed.
+	pub fn poll(&self) -> Option<Result<crate::reflect::Modules, String>> {
+		self.receiver.try_iter().last()
+	}
+}
+
+/// Builds the shader crate the way `build.rs` does and loads the module of every entry point.
+fn build() -> Result<crate::reflect::Modules, String> {
+	let result = crate::shader_build::builder(SHADER_DIR)
+		.print_metadata(spirv_builder::MetadataPrintout::None)
+		.build()
+		.map_err(|e| e.to_string())?;
+	let mut modules = crate::reflect::Modules::default();
+	for path in result.module.unwrap_multi().values() {
+		let spirv = crate::reflect::load(path)
+			.map_err(|e| format!("{}: {}", path.display(), crate::report(&e)))?;
+		modules.push(spirv);
+	}
+	Ok(modules)
+}
+
+/// The most recent modification time of any file in the shader crate outside `target`.
+fn latest_modification() -> Option<std::time::SystemTime> {
+	let mut latest = None;
+	let mut directories = vec![std::path::PathBuf::from(SHADER_DIR)];
+	while let Some(directory) = directories.pop() {
+		let Ok(entries) = std::fs::read_dir(&directory) else {
+			continue;
+		};
+		for entry in entries.flatten() {
+			let path = entry.path();
+			if path.is_dir() {
+				if entry.file_name() != "target" && entry.file_name() != "golden" {
+					directories.push(path);
+				}
+			} else if let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) {
+				latest = latest.max(Some(modified));
+			}
+		}
+	}
+	latest
+}
@@ -0,0 +1,766 @@
+//! Keyboard, mouse and gamepad state and the actions bound to them.
+//!
+//! [`crate::run`] feeds every window event and the events of the gamepad backend to the engine's
+//! [`Input`], which [`crate::App::update`] reads. Presses and releases are kept as "just pressed"
+//! and "just released" until the end of the next fixed tick, so every one of them is seen by
+//! exactly one update however many ticks a frame runs. Games query actions by name rather than
+//! keys, and the [`ActionMap`] binding them can be loaded from a file so players can rebind them:
+//!
+//! ```text
+//! # action = bindings, separated by commas
+//! jump = Space, MouseLeft
+//! left = KeyA, ArrowLeft, GamepadDPadLeft
+//! ```
+
+use crate::gamepad::{
+	GamepadAxis, GamepadBackend, GamepadButton, GamepadError, GamepadEvent, GamepadId, Rumble,
+};
+use shader::glam::{vec2, Vec2};
+use winit::event::{MouseScrollDelta, WindowEvent};
+pub use winit::{event::MouseButton, keyboard::KeyCode};
+
+/// Pixels scrolled by a line of a [`MouseScrollDelta::LineDelta`], to report pixel deltas from
+/// touchpads in lines.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// How far a trigger has to be pulled for [`GamepadButton::LeftTrigger`] or
+/// [`GamepadButton::RightTrigger`] to be pressed, for gamepads that only report the axis.
+const TRIGGER_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Pressed buttons of one kind, and their changes since the end of the last tick.
+struct Buttons<T> {
+	pressed: std::collections::HashSet<T>,
+	just_pressed: std::collections::HashSet<T>,
+	just_released: std::collections::HashSet<T>,
+}
+
+impl<T> Default for Buttons<T> {
+	fn default() -> Self {
+		Self {
+			pressed: Default::default(),
+			just_pressed: Default::default(),
+			just_released: Default::default(),
+		}
+	}
+}
+
+impl<T: Copy + Eq + std::hash::Hash> Buttons<T> {
+	fn set(&mut self, button: T, pressed: bool) {
+		if pressed {
+			// Key repeats arrive as further presses.
+			if self.pressed.insert(button) {
+				self.just_pressed.insert(button);
+			}
+		} else if self.pressed.remove(&button) {
+			self.just_released.insert(button);
+		}
+	}
+
+	fn release_all(&mut self) {
+		self.just_released.extend(self.pressed.drain());
+	}
+
+	fn end_tick(&mut self) {
+		self.just_pressed.clear();
+		self.just_released.clear();
+	}
+}
+
+/// Something an action can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+	Key(KeyCode),
+	Mouse(MouseButton),
+	/// The button on any connected gamepad.
+	Gamepad(GamepadButton),
+}
+
+/// Generates the names bindings are written with in action map files, which are the names of
+/// the variants. Every [`KeyCode`] winit knows is listed, so only [`MouseButton::Other`] has no
+/// name.
+macro_rules! binding_names {
+	(
+		keys: $($key:ident),* ;
+		mouse: $($mouse_name:ident => $mouse:ident),* ;
+		gamepad: $($gamepad_name:ident => $gamepad:ident),* $(,)?
+	) => {
+		impl Binding {
+			/// The binding written as `name` in an action map file, e.g. `KeyW`, `Space`,
+			/// `MouseLeft` or `GamepadSouth`.
+			pub fn from_name(name: &str) -> Option<Self> {
+				match name {
+					$(stringify!($key) => Some(Self::Key(KeyCode::$key)),)*
+					$(stringify!($mouse_name) => Some(Self::Mouse(MouseButton::$mouse)),)*
+					$(stringify!($gamepad_name) => Some(Self::Gamepad(GamepadButton::$gamepad)),)*
+					_ => None,
+				}
+			}
+
+			/// How the binding is written in an action map file, `None` for buttons that can't
+			/// be.
+			pub fn name(&self) -> Option<&'static str> {
+				match self {
+					$(Self::Key(KeyCode::$key) => Some(stringify!($key)),)*
+					$(Self::Mouse(MouseButton::$mouse) => Some(stringify!($mouse_name)),)*
+					$(Self::Gamepad(GamepadButton::$gamepad) => Some(stringify!($gamepad_name)),)*
+					_ => None,
+				}
+			}
+		}
+	};
+}
+
+binding_names! {
+	keys: Backquote, Backslash, BracketLeft, BracketRight, Comma, Digit0, Digit1, Digit2, Digit3,
+		Digit4, Digit5, Digit6, Digit7, Digit8, Digit9, Equal, IntlBackslash, IntlRo, IntlYen, KeyA,
+		KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN, KeyO, KeyP,
+		KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ, Minus, Period, Quote, Semicolon,
+		Slash, AltLeft, AltRight, Backspace, CapsLock, ContextMenu, ControlLeft, ControlRight,
+		Enter, SuperLeft, SuperRight, ShiftLeft, ShiftRight, Space, Tab, Convert, KanaMode, Lang1,
+		Lang2, Lang3, Lang4, Lang5, NonConvert, Delete, End, Help, Home, Insert, PageDown, PageUp,
+		ArrowDown, ArrowLeft, ArrowRight, ArrowUp, NumLock, Numpad0, Numpad1, Numpad2, Numpad3,
+		Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, NumpadAdd, NumpadBackspace,
+		NumpadClear, NumpadClearEntry, NumpadComma, NumpadDecimal, NumpadDivide, NumpadEnter,
+		NumpadEqual, NumpadHash, NumpadMemoryAdd, NumpadMemoryClear, NumpadMemoryRecall,
+		NumpadMemoryStore, NumpadMemorySubtract, NumpadMultiply, NumpadParenLeft, NumpadParenRight,
+		NumpadStar, NumpadSubtract, Escape, Fn, FnLock, PrintScreen, ScrollLock, Pause, BrowserBack,
+		BrowserFavorites, BrowserForward, BrowserHome, BrowserRefresh, BrowserSearch, BrowserStop,
+		Eject, LaunchApp1, LaunchApp2, LaunchMail, MediaPlayPause, MediaSelect, MediaStop,
+		MediaTrackNext, MediaTrackPrevious, Power, Sleep, AudioVolumeDown, AudioVolumeMute,
+		AudioVolumeUp, WakeUp, Meta, Hyper, Turbo, Abort, Resume, Suspend, Again, Copy, Cut, Find,
+		Open, Paste, Props, Select, Undo, Hiragana, Katakana, F1, F2, F3, F4, F5, F6, F7, F8, F9,
+		F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25, F26, F27,
+		F28, F29, F30, F31, F32, F33, F34, F35;
+	mouse: MouseLeft => Left, MouseRight => Right, MouseMiddle => Middle, MouseBack => Back,
+		MouseForward => Forward;
+	gamepad: GamepadSouth => South, GamepadEast => East, GamepadNorth => North,
+		GamepadWest => West, GamepadLeftShoulder => LeftShoulder,
+		GamepadRightShoulder => RightShoulder, GamepadLeftTrigger => LeftTrigger,
+		GamepadRightTrigger => RightTrigger, GamepadSelect => Select, GamepadStart => Start,
+		GamepadMode => Mode, GamepadLeftStick => LeftStick, GamepadRightStick => RightStick,
+		GamepadDPadUp => DPadUp, GamepadDPadDown => DPadDown, GamepadDPadLeft => DPadLeft,
+		GamepadDPadRight => DPadRight,
+}
+
+/// Which bindings trigger each action. An action is pressed while any of its bindings is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ActionMap {
+	actions: std::collections::BTreeMap<String, Vec<Binding>>,
+}
+
+impl ActionMap {
+	/// Reads an action map file, see the [module docs](self).
+	pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, InputError> {
+		Self::parse(&std::fs::read_to_string(path)?)
+	}
+
+	/// Parses the contents of an action map file.
+	pub fn parse(source: &str) -> Result<Self, InputError> {
+		let mut map = Self::default();
+		for (index, line) in source.lines().enumerate() {
+			let line_number = index + 1;
+			let line = line.split('#').next().unwrap_or_default().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let Some((action, bindings)) = line.split_once('=') else {
+				return Err(InputError::Syntax { line: line_number });
+			};
+			let action = action.trim();
+			if action.is_empty() {
+				return Err(InputError::Syntax { line: line_number });
+			}
+			let bindings = bindings
+				.split(',')
+				.map(str::trim)
+				.filter(|name| !name.is_empty())
+				.map(|name| {
+					Binding::from_name(name).ok_or_else(|| InputError::UnknownBinding {
+						line: line_number,
+						name: name.to_owned(),
+					})
+				})
+				.collect::<Result<_, _>>()?;
+			map.rebind(action, bindings);
+		}
+		Ok(map)
+	}
+
+	/// Writes the map in the format [`Self::load`] reads, skipping bindings without a
+	/// [`Binding::name`].
+	pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+		std::fs::write(path, self.to_string())
+	}
+
+	/// Adds `binding` to the bindings of `action`.
+	pub fn bind(&mut self, action: &str, binding: Binding) -> &mut Self {
+		let bindings = self.actions.entry(action.to_owned()).or_default();
+		if !bindings.contains(&binding) {
+			bindings.push(binding);
+		}
+		self
+	}
+
+	/// Replaces the bindings of `action`.
+	pub fn rebind(&mut self, action: &str, bindings: Vec<Binding>) -> &mut Self {
+		self.actions.insert(action.to_owned(), bindings);
+		self
+	}
+
+	/// Removes `action` and its bindings.
+	pub fn unbind(&mut self, action: &str) -> &mut Self {
+		self.actions.remove(action);
+		self
+	}
+
+	/// Bindings of `action`, empty if it isn't bound.
+	pub fn bindings(&self, action: &str) -> &[Binding] {
+		self.actions.get(action).map_or(&[], Vec::as_slice)
+	}
+
+	/// Names of the bound actions, in alphabetical order.
+	pub fn actions(&self) -> impl Iterator<Item = &str> {
+		self.actions.keys().map(String::as_str)
+	}
+}
+
+impl std::fmt::Display for ActionMap {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (action, bindings) in &self.actions {
+			write!(f, "{action} =")?;
+			let names = bindings.iter().filter_map(Binding::name);
+			for (i, name) in names.enumerate() {
+				write!(f, "{} {name}", if i == 0 { "" } else { "," })?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub enum InputError {
+	Io(std::io::Error),
+	/// A line of an action map file isn't `action = bindings`.
+	Syntax {
+		line: usize,
+	},
+	/// An action map file binds an action to something that isn't a [`Binding::name`].
+	UnknownBinding {
+		line: usize,
+		name: String,
+	},
+}
+
+impl std::fmt::Display for InputError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(_) => write!(f, "failed to read the action map"),
+			Self::Syntax { line } => write!(f, "line {line}: expected `action = bindings`"),
+			Self::UnknownBinding { line, name } => {
+				write!(f, "line {line}: unknown binding {name:?}")
+			},
+		}
+	}
+}
+
+impl std::error::Error for InputError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Syntax { .. } | Self::UnknownBinding { .. } => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for InputError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+/// A change to the input state. Window events are reduced to these, which unlike them can be
+/// recorded and replayed, see [`crate::replay`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+	Key {
+		key: KeyCode,
+		pressed: bool,
+	},
+	MouseButton {
+		button: MouseButton,
+		pressed: bool,
+	},
+	/// Pixels from the top left corner of the window.
+	CursorMoved(Vec2),
+	CursorLeft,
+	/// Lines scrolled, positive `y` scrolling up.
+	Scroll(Vec2),
+	/// New inner size of the window in pixels.
+	Resized([u32; 2]),
+	FocusLost,
+	Gamepad(GamepadEvent),
+}
+
+impl InputEvent {
+	/// The part of `event` the input state depends on, if any. Key repeats are kept, as the
+	/// state ignores them anyway.
+	pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+		Some(match event {
+			WindowEvent::KeyboardInput { event, .. } => match event.physical_key {
+				winit::keyboard::PhysicalKey::Code(key) => Self::Key {
+					key,
+					pressed: event.state.is_pressed(),
+				},
+				winit::keyboard::PhysicalKey::Unidentified(_) => return None,
+			},
+			WindowEvent::MouseInput { state, button, .. } => Self::MouseButton {
+				button: *button,
+				pressed: state.is_pressed(),
+			},
+			WindowEvent::CursorMoved { position, .. } => {
+				Self::CursorMoved(vec2(position.x as f32, position.y as f32))
+			},
+			WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+			WindowEvent::MouseWheel { delta, .. } => Self::Scroll(match delta {
+				MouseScrollDelta::LineDelta(x, y) => vec2(*x, *y),
+				MouseScrollDelta::PixelDelta(position) => {
+					vec2(position.x as f32, position.y as f32) / PIXELS_PER_LINE
+				},
+			}),
+			WindowEvent::Resized(size) => Self::Resized([size.width, size.height]),
+			WindowEvent::Focused(false) => Self::FocusLost,
+			_ => return None,
+		})
+	}
+}
+
+/// Stick and trigger values below which a gamepad reports 0, as a fraction of their range.
+/// Values above are rescaled to still cover the whole range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deadzones {
+	/// Applied to the distance of a stick from its center, so it doesn't favor the diagonals.
+	pub stick: f32,
+	pub trigger: f32,
+}
+
+impl Default for Deadzones {
+	fn default() -> Self {
+		Self {
+			stick: 0.15,
+			trigger: 0.05,
+		}
+	}
+}
+
+/// The state of a connected gamepad.
+pub struct Gamepad {
+	name: String,
+	buttons: Buttons<GamepadButton>,
+	/// Values as reported, before deadzones.
+	axes: std::collections::HashMap<GamepadAxis, f32>,
+	deadzones: Deadzones,
+}
+
+impl Gamepad {
+	/// The product name reported by the device.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn pressed(&self, button: GamepadButton) -> bool {
+		self.buttons.pressed.contains(&button)
+	}
+
+	/// Whether `button` was pressed since the end of the last tick.
+	pub fn just_pressed(&self, button: GamepadButton) -> bool {
+		self.buttons.just_pressed.contains(&button)
+	}
+
+	/// Whether `button` was released since the end of the last tick.
+	pub fn just_released(&self, button: GamepadButton) -> bool {
+		self.buttons.just_released.contains(&button)
+	}
+
+	/// The value of `axis` with deadzones applied, 0 if the gamepad doesn't have it.
+	pub fn axis(&self, axis: GamepadAxis) -> f32 {
+		match axis {
+			GamepadAxis::LeftX => self.left_stick().x,
+			GamepadAxis::LeftY => self.left_stick().y,
+			GamepadAxis::RightX => self.right_stick().x,
+			GamepadAxis::RightY => self.right_stick().y,
+			GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => {
+				apply_deadzone(self.raw_axis(axis), self.deadzones.trigger)
+			},
+		}
+	}
+
+	/// Position of the left stick with its deadzone applied, `y` pointing down.
+	pub fn left_stick(&self) -> Vec2 {
+		self.stick(GamepadAxis::LeftX, GamepadAxis::LeftY)
+	}
+
+	/// Position of the right stick with its deadzone applied, `y` pointing down.
+	pub fn right_stick(&self) -> Vec2 {
+		self.stick(GamepadAxis::RightX, GamepadAxis::RightY)
+	}
+
+	fn stick(&self, x: GamepadAxis, y: GamepadAxis) -> Vec2 {
+		let position = vec2(self.raw_axis(x), self.raw_axis(y));
+		let distance = position.length();
+		if distance == 0.0 {
+			return Vec2::ZERO;
+		}
+		position / distance * apply_deadzone(distance.min(1.0), self.deadzones.stick)
+	}
+
+	fn raw_axis(&self, axis: GamepadAxis) -> f32 {
+		self.axes.get(&axis).copied().unwrap_or_default()
+	}
+
+	fn handle_event(&mut self, event: &GamepadEvent) {
+		match *event {
+			GamepadEvent::Button {
+				button, pressed, ..
+			} => self.buttons.set(button, pressed),
+			GamepadEvent::Axis { axis, value, .. } => {
+				self.axes.insert(axis, value);
+				let button = match axis {
+					GamepadAxis::LeftTrigger => GamepadButton::LeftTrigger,
+					GamepadAxis::RightTrigger => GamepadButton::RightTrigger,
+					_ => return,
+				};
+				self.buttons.set(button, value > TRIGGER_PRESS_THRESHOLD);
+			},
+			GamepadEvent::Connected { .. } | GamepadEvent::Disconnected { .. } => (),
+		}
+	}
+}
+
+/// Maps `value` from `deadzone..=1` to `0..=1`.
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+	if value <= deadzone {
+		0.0
+	} else {
+		(value - deadzone) / (1.0 - deadzone)
+	}
+}
+
+/// The state of the keyboard, mouse and gamepads, updated from window events and the gamepad
+/// backend.
+#[derive(Default)]
+pub struct Input {
+	keys: Buttons<KeyCode>,
+	mouse_buttons: Buttons<MouseButton>,
+	gamepads: std::collections::BTreeMap<GamepadId, Gamepad>,
+	/// Gamepads connected and disconnected since the end of the last tick.
+	just_connected: Vec<GamepadId>,
+	just_disconnected: Vec<GamepadId>,
+	gamepad_backend: Option<Box<dyn GamepadBackend>>,
+	deadzones: Deadzones,
+	/// `None` until the cursor first moves over the window, and after it leaves.
+	cursor: Option<Vec2>,
+	window_size: Vec2,
+	/// Lines scrolled since the end of the last tick.
+	scroll: Vec2,
+	pub actions: ActionMap,
+}
+
+impl Input {
+	pub fn new(actions: ActionMap) -> Self {
+		Self {
+			actions,
+			..Default::default()
+		}
+	}
+
+	pub fn key_pressed(&self, key: KeyCode) -> bool {
+		self.keys.pressed.contains(&key)
+	}
+
+	/// Whether `key` was pressed since the end of the last tick.
+	pub fn key_just_pressed(&self, key: KeyCode) -> bool {
+		self.keys.just_pressed.contains(&key)
+	}
+
+	/// Whether `key` was released since the end of the last tick.
+	pub fn key_just_released(&self, key: KeyCode) -> bool {
+		self.keys.just_released.contains(&key)
+	}
+
+	pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+		self.mouse_buttons.pressed.contains(&button)
+	}
+
+	/// Whether `button` was pressed since the end of the last tick.
+	pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+		self.mouse_buttons.just_pressed.contains(&button)
+	}
+
+	/// Whether `button` was released since the end of the last tick.
+	pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+		self.mouse_buttons.just_released.contains(&button)
+	}
+
+	/// Whether any binding of `action` is pressed.
+	pub fn action_pressed(&self, action: &str) -> bool {
+		self.actions
+			.bindings(action)
+			.iter()
+			.any(|binding| self.binding_pressed(binding))
+	}
+
+	/// Whether a binding of `action` was pressed since the end of the last tick.
+	pub fn action_just_pressed(&self, action: &str) -> bool {
+		self.actions
+			.bindings(action)
+			.iter()
+			.any(|binding| match binding {
+				Binding::Key(key) => self.key_just_pressed(*key),
+				Binding::Mouse(button) => self.mouse_just_pressed(*button),
+				Binding::Gamepad(button) => self
+					.gamepads()
+					.any(|(_, gamepad)| gamepad.just_pressed(*button)),
+			})
+	}
+
+	/// Whether a binding of `action` was released since the end of the last tick and none is
+	/// pressed anymore.
+	pub fn action_just_released(&self, action: &str) -> bool {
+		let bindings = self.actions.bindings(action);
+		bindings.iter().any(|binding| match binding {
+			Binding::Key(key) => self.key_just_released(*key),
+			Binding::Mouse(button) => self.mouse_just_released(*button),
+			Binding::Gamepad(button) => self
+				.gamepads()
+				.any(|(_, gamepad)| gamepad.just_released(*button)),
+		}) && !self.action_pressed(action)
+	}
+
+	/// The connected gamepads, in the order they were connected.
+	pub fn gamepads(&self) -> impl Iterator<Item = (GamepadId, &Gamepad)> {
+		self.gamepads.iter().map(|(id, gamepad)| (*id, gamepad))
+	}
+
+	/// `None` if `gamepad` isn't connected.
+	pub fn gamepad(&self, gamepad: GamepadId) -> Option<&Gamepad> {
+		self.gamepads.get(&gamepad)
+	}
+
+	/// Gamepads connected since the end of the last tick.
+	pub fn just_connected(&self) -> &[GamepadId] {
+		&self.just_connected
+	}
+
+	/// Gamepads disconnected since the end of the last tick.
+	pub fn just_disconnected(&self) -> &[GamepadId] {
+		&self.just_disconnected
+	}
+
+	pub fn deadzones(&self) -> Deadzones {
+		self.deadzones
+	}
+
+	/// Sets the deadzones of every gamepad, including ones connected later.
+	pub fn set_deadzones(&mut self, deadzones: Deadzones) {
+		self.deadzones = deadzones;
+		for gamepad in self.gamepads.values_mut() {
+			gamepad.deadzones = deadzones;
+		}
+	}
+
+	/// Vibrates `gamepad`, replacing any rumble still playing on it.
+	pub fn rumble(&mut self, gamepad: GamepadId, rumble: Rumble) -> Result<(), GamepadError> {
+		match &mut self.gamepad_backend {
+			Some(backend) => backend.rumble(gamepad, rumble),
+			None => Err(GamepadError::NotConnected(gamepad)),
+		}
+	}
+
+	/// Replaces the source of gamepads, e.g. with a [`crate::gamepad::FakeGamepads`]. The
+	/// gamepads of the previous backend are disconnected.
+	pub fn set_gamepad_backend(&mut self, backend: Option<Box<dyn GamepadBackend>>) {
+		let gamepads = self.gamepads.keys().copied().collect::<Vec<_>>();
+		for gamepad in gamepads {
+			self.handle_gamepad_event(&GamepadEvent::Disconnected { gamepad });
+		}
+		self.gamepad_backend = backend;
+	}
+
+	/// Cursor position in pixels from the top left corner of the window, `None` while it's
+	/// outside the window.
+	pub fn cursor(&self) -> Option<Vec2> {
+		self.cursor
+	}
+
+	/// Cursor position from `(0, 0)` at the top left corner of the window to `(1, 1)` at the bottom
+	/// right.
+	pub fn cursor_normalized(&self) -> Option<Vec2> {
+		Some(self.cursor? / self.window_size.max(Vec2::ONE))
+	}
+
+	/// Lines scrolled since the end of the last tick, positive `y` scrolling up.
+	pub fn scroll(&self) -> Vec2 {
+		self.scroll
+	}
+
+	/// Updates the state from `event`.
+	pub fn handle(&mut self, event: &InputEvent) {
+		match event {
+			InputEvent::Key { key, pressed } => self.keys.set(*key, *pressed),
+			InputEvent::MouseButton { button, pressed } => {
+				self.mouse_buttons.set(*button, *pressed)
+			},
+			InputEvent::CursorMoved(position) => self.cursor = Some(*position),
+			InputEvent::CursorLeft => self.cursor = None,
+			InputEvent::Scroll(lines) => self.scroll += *lines,
+			InputEvent::Resized(size) => self.window_size = vec2(size[0] as f32, size[1] as f32),
+			// Releases while unfocused are never delivered.
+			InputEvent::FocusLost => {
+				self.keys.release_all();
+				self.mouse_buttons.release_all();
+			},
+			InputEvent::Gamepad(event) => self.handle_gamepad_event(event),
+		}
+	}
+
+	fn handle_gamepad_event(&mut self, event: &GamepadEvent) {
+		match event {
+			GamepadEvent::Connected { gamepad, name } => {
+				self.gamepads.insert(
+					*gamepad,
+					Gamepad {
+						name: name.clone(),
+						buttons: Buttons::default(),
+						axes: Default::default(),
+						deadzones: self.deadzones,
+					},
+				);
+				self.just_connected.push(*gamepad);
+			},
+			GamepadEvent::Disconnected { gamepad } => {
+				if self.gamepads.remove(gamepad).is_some() {
+					self.just_disconnected.push(*gamepad);
+				}
+			},
+			GamepadEvent::Button { gamepad, .. } | GamepadEvent::Axis { gamepad, .. } => {
+				if let Some(state) = self.gamepads.get_mut(gamepad) {
+					state.handle_event(event);
+				}
+			},
+		}
+	}
+
+	/// Returns the events of the gamepad backend since the last call, to be passed to
+	/// [`Self::handle`].
+	pub(crate) fn poll_gamepad_backend(&mut self) -> Vec<GamepadEvent> {
+		match &mut self.gamepad_backend {
+			Some(backend) => backend.poll(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Forgets the presses, releases and scrolling seen by the tick that just ran.
+	pub(crate) fn end_tick(&mut self) {
+		self.keys.end_tick();
+		self.mouse_buttons.end_tick();
+		for gamepad in self.gamepads.values_mut() {
+			gamepad.buttons.end_tick();
+		}
+		self.just_connected.clear();
+		self.just_disconnected.clear();
+		self.scroll = Vec2::ZERO;
+	}
+
+	fn binding_pressed(&self, binding: &Binding) -> bool {
+		match binding {
+			Binding::Key(key) => self.key_pressed(*key),
+			Binding::Mouse(button) => self.mouse_pressed(*button),
+			Binding::Gamepad(button) => {
+				self.gamepads().any(|(_, gamepad)| gamepad.pressed(*button))
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SOURCE: &str = "
+		# Movement
+		jump = Space, MouseLeft   # and the south button:
+		jump = Space, MouseLeft, GamepadSouth
+		left = KeyA, ArrowLeft, GamepadDPadLeft
+
+		pause =
+	";
+
+	#[test]
+	fn parses_action_maps() {
+		let map = ActionMap::parse(SOURCE).unwrap();
+		assert_eq!(map.actions().collect::<Vec<_>>(), ["jump", "left", "pause"]);
+		assert_eq!(
+			map.bindings("jump"),
+			[
+				Binding::Key(KeyCode::Space),
+				Binding::Mouse(MouseButton::Left),
+				Binding::Gamepad(GamepadButton::South),
+			],
+		);
+		assert_eq!(
+			map.bindings("left"),
+			[
+				Binding::Key(KeyCode::KeyA),
+				Binding::Key(KeyCode::ArrowLeft),
+				Binding::Gamepad(GamepadButton::DPadLeft),
+			],
+		);
+		assert!(map.bindings("pause").is_empty());
+		assert!(map.bindings("crouch").is_empty());
+	}
+
+	#[test]
+	fn round_trips_through_display() {
+		let map = ActionMap::parse(SOURCE).unwrap();
+		let written = map.to_string();
+		assert_eq!(
+			written,
+			"jump = Space, MouseLeft, GamepadSouth\nleft = KeyA, ArrowLeft, GamepadDPadLeft\npause =\n",
+		);
+		assert_eq!(ActionMap::parse(&written).unwrap(), map);
+	}
+
+	#[test]
+	fn names_round_trip() {
+		let bindings = [
+			Binding::Key(KeyCode::KeyW),
+			Binding::Key(KeyCode::NumpadEnter),
+			Binding::Mouse(MouseButton::Forward),
+			Binding::Gamepad(GamepadButton::RightTrigger),
+		];
+		for binding in bindings {
+			let name = binding.name().unwrap();
+			assert_eq!(Binding::from_name(name), Some(binding));
+		}
+		assert_eq!(Binding::Key(KeyCode::F24).name(), Some("F24"));
+		assert_eq!(Binding::Mouse(MouseButton::Other(8)).name(), None);
+		assert_eq!(Binding::from_name("KeyÄ"), None);
+	}
+
+	#[test]
+	fn rejects_malformed_lines() {
+		assert!(matches!(
+			ActionMap::parse("jump = Space\n\njump Space"),
+			Err(InputError::Syntax { line: 3 })
+		));
+		assert!(matches!(
+			ActionMap::parse(" = Space"),
+			Err(InputError::Syntax { line: 1 })
+		));
+		assert!(matches!(
+			ActionMap::parse("jump = Space, Spcae"),
+			Err(InputError::UnknownBinding { line: 1, name }) if name == "Spcae"
+		));
+	}
+}
@@ -0,0 +1,557 @@
+//! Dot, a small engine rendering with compute shaders written in Rust.
+//!
+//! A game implements [`App`] and hands it to [`run`], which opens the window, creates the
+//! [`Renderer`] and drives the app's updates and frames with a fixed-timestep [`GameLoop`],
+//! running the systems of the game's [`ecs::World`] along the way, or of its own world declared
+//! with [`world!`].
+
+use winit::{
+	event::{ElementState, Event, KeyEvent, WindowEvent},
+	event_loop::ControlFlow,
+	keyboard::{KeyCode, PhysicalKey},
+};
+
+pub mod audio;
+pub mod bench;
+pub mod config;
+pub mod device;
+pub mod ecs;
+mod error;
+pub mod game_loop;
+pub mod gamepad;
+pub mod golden;
+pub mod graph;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+pub mod input;
+pub mod reflect;
+mod renderer;
+pub mod replay;
+pub mod screenshot;
+#[cfg(feature = "hot-reload")]
+mod shader_build;
+pub mod window;
+/// Entry points of the shader crate, generated by `build.rs`.
+pub mod shaders {
+	include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+
+pub use config::Config;
+pub use device::DeviceSelection;
+pub use error::RendererError;
+pub use game_loop::{App, Frame, GameLoop, Init, LoopConfig, LoopConfigError, Tick, Update};
+pub(crate) use renderer::workgroup_count;
+pub use renderer::Renderer;
+pub use shader::glam;
+
+/// Runs `app` until its window is closed or [`Config::frames`] were rendered. Headless runs
+/// render one frame by default, or the frames of the recording with [`Config::replay`].
+pub fn run<W: ecs::EngineWorld>(
+	app: impl App<W>,
+	config: Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+	let headless = config.headless || config.replay.is_some();
+	let audio = audio::Audio::new(if headless {
+		audio::AudioOutput::Null
+	} else {
+		config.audio
+	});
+	let mut input = input::Input::new(config.actions);
+	if !headless {
+		input.set_gamepad_backend(gamepad::default_backend());
+	}
+	let mut engine = Engine::new(app, config.game_loop.clone(), audio, input)
+		.map_err(|e| format!("invalid loop config: {e}"))?;
+	if let Some(path) = &config.record {
+		let recorder = replay::Recorder::create(path)
+			.map_err(|e| format!("failed to create recording {}: {e}", path.display()))?;
+		engine.recorder = Some(recorder);
+	}
+	let mut screenshot_path = config.screenshot;
+
+	if headless {
+		let recording = match &config.replay {
+			Some(path) => Some(replay::Recording::load(path).map_err(|e| {
+				format!(
+					"failed to load recording {}: {}",
+					path.display(),
+					report(&e)
+				)
+			})?),
+			None => None,
+		};
+		let mut renderer = Renderer::new_headless(
+			config.window.size,
+			config.app_version,
+			&config.shaders,
+			&graph::RenderGraph::default(),
+			&config.device_selection,
+		)
+		.map_err(|e| format!("failed to create headless renderer: {}", report(&e)))?;
+		engine.input_event(input::InputEvent::Resized(config.window.size));
+		engine
+			.init(&mut renderer)
+			.map_err(|e| format!("failed to initialize app: {}", report(&e)))?;
+		if screenshot_path.is_some() {
+			renderer.request_capture();
+		}
+		match &recording {
+			Some(recording) => {
+				let alpha = engine
+					.replay(recording)
+					.map_err(|e| format!("failed to replay recording: {}", report(&e)))?;
+				engine
+					.render(&mut renderer, config.window.size, alpha)
+					.map_err(|e| format!("failed to render replayed frame: {}", report(&e)))?;
+			},
+			None => {
+				for _ in 0..config.frames.unwrap_or(1) {
+					engine
+						.frame(&mut renderer, config.window.size)
+						.map_err(|e| format!("failed to render headless frame: {}", report(&e)))?;
+				}
+			},
+		}
+		if let (Some(frame), Some(path)) = (renderer.take_captured_frame(), screenshot_path) {
+			save_screenshot(&frame, &path);
+		}
+		renderer.wait_idle()?;
+		match recording {
+			Some(recording) => println!(
+				"Replayed {} frames",
+				recording
+					.records
+					.iter()
+					.filter(|record| matches!(record, replay::Record::Frame(_)))
+					.count()
+			),
+			None => println!("Rendered {} headless frames", config.frames.unwrap_or(1)),
+		}
+		return Ok(());
+	}
+
+	let event_loop = winit::event_loop::EventLoop::new()?;
+
+	let window = window::Window::new(&event_loop, config.window)
+		.map_err(|e| format!("failed to create window: {}", report(&e)))?;
+
+	let required_extensions = vulkano::swapchain::Surface::required_extensions(&event_loop);
+
+	let mut renderer = Renderer::new(
+		window.winit_window().clone(),
+		required_extensions,
+		config.app_version,
+		&config.shaders,
+		&graph::RenderGraph::default(),
+		&config.device_selection,
+		&config.surface,
+	)
+	.map_err(|e| format!("failed to create renderer: {}", report(&e)))?;
+	engine.input_event(input::InputEvent::Resized(
+		window.winit_window().inner_size().into(),
+	));
+	let winit_window = window.winit_window().clone();
+	engine.window = Some(window);
+	engine
+		.init(&mut renderer)
+		.map_err(|e| format!("failed to initialize app: {}", report(&e)))?;
+	if screenshot_path.is_some() {
+		renderer.request_capture();
+	}
+
+	#[cfg(feature = "hot-reload")]
+	let shader_watcher = hot_reload::ShaderWatcher::spawn();
+
+	let mut frames_rendered = 0;
+
+	event_loop.run(move |event, elwt| {
+		elwt.set_control_flow(ControlFlow::Poll);
+
+		match event {
+			Event::WindowEvent { event, .. } => {
+				if let Some(event) = input::InputEvent::from_window_event(&event) {
+					engine.input_event(event);
+				}
+				match event {
+					WindowEvent::CloseRequested => elwt.exit(),
+					WindowEvent::Resized(_) => renderer.recreate_swapchain(true),
+					WindowEvent::KeyboardInput {
+						event:
+							KeyEvent {
+								physical_key: PhysicalKey::Code(KeyCode::F12),
+								state: ElementState::Pressed,
+								repeat: false,
+								..
+							},
+						..
+					} => renderer.request_capture(),
+					WindowEvent::RedrawRequested => {
+						let image_extent: [u32; 2] = winit_window.inner_size().into();
+						if image_extent.contains(&0) {
+							return;
+						}
+						if let Err(e) = engine.frame(&mut renderer, image_extent) {
+							println!("failed to render frame: {}", report(&e));
+							elwt.exit();
+							return;
+						}
+
+						if let Some(frame) = renderer.take_captured_frame() {
+							let path = screenshot_path
+								.take()
+								.unwrap_or_else(default_screenshot_path);
+							save_screenshot(&frame, &path);
+						}
+
+						frames_rendered += 1;
+						if config.frames == Some(frames_rendered) {
+							elwt.exit();
+						}
+					},
+					_ => (),
+				}
+			},
+			Event::AboutToWait => {
+				#[cfg(feature = "hot-reload")]
+				match shader_watcher.poll() {
+					Some(Ok(shaders)) => match renderer.reload_shader(&shaders) {
+						Ok(()) => println!("Reloaded shader"),
+						Err(e) => println!(
+							"failed to reload shader, keeping the previous one: {}",
+							report(&e)
+						),
+					},
+					Some(Err(e)) => {
+						println!("failed to rebuild shader, keeping the previous one: {e}")
+					},
+					None => (),
+				}
+				match engine.game_loop.next_frame_at() {
+					Some(next_frame) if std::time::Instant::now() < next_frame => {
+						elwt.set_control_flow(ControlFlow::WaitUntil(next_frame))
+					},
+					_ => winit_window.request_redraw(),
+				}
+			},
+			_ => (),
+		}
+	})?;
+	Ok(())
+}
+
+/// An app with the world and systems it runs on and the engine's subsystems.
+struct Engine<A, W> {
+	app: A,
+	world: W,
+	schedule: ecs::Schedule<W>,
+	audio: audio::Audio,
+	input: input::Input,
+	game_loop: GameLoop,
+	/// Simulated time since the first frame, the sum of the loop's frame times.
+	elapsed: std::time::Duration,
+	/// `None` when headless.
+	window: Option<window::Window>,
+	recorder: Option<replay::Recorder>,
+}
+
+impl<W: ecs::EngineWorld, A: App<W>> Engine<A, W> {
+	fn new(
+		app: A,
+		loop_config: LoopConfig,
+		audio: audio::Audio,
+		input: input::Input,
+	) -> Result<Self, LoopConfigError> {
+		Ok(Self {
+			app,
+			world: W::default(),
+			schedule: ecs::Schedule::default(),
+			audio,
+			input,
+			game_loop: GameLoop::new(loop_config)?,
+			elapsed: std::time::Duration::ZERO,
+			window: None,
+			recorder: None,
+		})
+	}
+
+	/// Initializes the app, then runs the startup systems.
+	fn init(&mut self, renderer: &mut Renderer) -> Result<(), RendererError> {
+		self.app.init(Init {
+			renderer,
+			world: &mut self.world,
+			schedule: &mut self.schedule,
+			audio: &mut self.audio,
+			input: &mut self.input,
+			window: self.window.as_mut(),
+		})?;
+		self.schedule.run_startup(&mut self.world);
+		Ok(())
+	}
+
+	/// Runs the ticks due and the per-frame systems, then renders a frame of `image_extent`.
+	fn frame(
+		&mut self,
+		renderer: &mut Renderer,
+		image_extent: [u32; 2],
+	) -> Result<(), RendererError> {
+		let (alpha, _) = self.step(None);
+		self.render(renderer, image_extent, alpha)
+	}
+
+	/// Runs the ticks due `frame_time` after the previous frame, or the time measured since then
+	/// if `None`, and the per-frame systems. Returns the interpolation alpha for rendering and the
+	/// indices of the ticks run.
+	fn step(&mut self, frame_time: Option<std::time::Duration>) -> (f32, Vec<u64>) {
+		for event in self.input.poll_gamepad_backend() {
+			self.input_event(input::InputEvent::Gamepad(event));
+		}
+
+		let Self {
+			app,
+			world,
+			schedule,
+			audio,
+			input,
+			game_loop,
+			..
+		} = self;
+		let mut ticks = Vec::new();
+		let update = |tick: &Tick| {
+			schedule.run_fixed_update(world, tick);
+			app.update(Update {
+				world,
+				audio,
+				input,
+				tick,
+			});
+			input.end_tick();
+			ticks.push(tick.index);
+		};
+		let alpha = match frame_time {
+			Some(frame_time) => game_loop.advance_by(frame_time, update),
+			None => game_loop.advance(update),
+		};
+		let frame_time = game_loop.frame_time();
+		schedule.run_update(world, frame_time);
+		audio.update(world, frame_time);
+		self.elapsed += frame_time;
+
+		self.record(&replay::Record::Frame(frame_time));
+		for &tick in &ticks {
+			self.record(&replay::Record::Tick(tick));
+		}
+		if let Some(Err(e)) = self.recorder.as_mut().map(replay::Recorder::flush) {
+			println!("failed to write recording, stopping recording: {e}");
+			self.recorder = None;
+		}
+		(alpha, ticks)
+	}
+
+	/// Extracts the world and renders a frame of `image_extent`. The shader's time and cursor
+	/// come from the loop and the input state rather than the clock and the window, so replays
+	/// render the same frames as the recorded run.
+	fn render(
+		&mut self,
+		renderer: &mut Renderer,
+		image_extent: [u32; 2],
+		alpha: f32,
+	) -> Result<(), RendererError> {
+		renderer.set_frame_clock(self.elapsed, self.game_loop.frame_time());
+		if let Some(cursor) = self.input.cursor() {
+			renderer.set_mouse_position(cursor.into());
+		}
+		renderer.set_extract(self.schedule.run_extract(&mut self.world, alpha));
+		self.app.render(Frame {
+			renderer,
+			world: &mut self.world,
+			window: self.window.as_mut(),
+			image_extent,
+			alpha,
+		})
+	}
+
+	/// Updates the input state from `event` and passes it to the app, recording it if recording.
+	fn input_event(&mut self, event: input::InputEvent) {
+		self.input.handle(&event);
+		self.app.input_event(&event);
+		self.record(&replay::Record::Event(event));
+	}
+
+	/// Feeds the recorded input to the app and runs the recorded frames. Returns the interpolation
+	/// alpha of the last frame.
+	fn replay(&mut self, recording: &replay::Recording) -> Result<f32, replay::ReplayError> {
+		let mut alpha = 0.0;
+		let mut frame = 0;
+		// Ticks run by the latest frame that the recording hasn't listed yet.
+		let mut ticks = std::collections::VecDeque::new();
+		for record in &recording.records {
+			match record {
+				replay::Record::Tick(tick) => {
+					if ticks.pop_front() != Some(*tick) {
+						return Err(replay::ReplayError::Diverged { frame, tick: *tick });
+					}
+				},
+				_ if !ticks.is_empty() => {
+					return Err(replay::ReplayError::Diverged {
+						frame,
+						tick: ticks[0],
+					});
+				},
+				replay::Record::Event(event) => self.input_event(event.clone()),
+				replay::Record::Frame(frame_time) => {
+					frame += 1;
+					let (frame_alpha, ran) = self.step(Some(*frame_time));
+					alpha = frame_alpha;
+					ticks.extend(ran);
+				},
+			}
+		}
+		match ticks.front() {
+			Some(&tick) => Err(replay::ReplayError::Diverged { frame, tick }),
+			None => Ok(alpha),
+		}
+	}
+
+	fn record(&mut self, record: &replay::Record) {
+		let Some(recorder) = &mut self.recorder else {
+			return;
+		};
+		if let Err(e) = recorder.record(record) {
+			println!("failed to write recording, stopping recording: {e}");
+			self.recorder = None;
+		}
+	}
+}
+
+/// Formats `error` followed by its chain of sources.
+pub fn report(error: &dyn std::error::Error) -> String {
+	let mut message = error.to_string();
+	let mut source = error.source();
+	while let Some(error) = source {
+		message += &format!(": {error}");
+		source = error.source();
+	}
+	message
+}
+
+fn default_screenshot_path() -> std::path::PathBuf {
+	let timestamp = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs();
+	format!("screenshot-{timestamp}.png").into()
+}
+
+fn save_screenshot(frame: &screenshot::Frame, path: &std::path::Path) {
+	match frame.save(path) {
+		Ok(()) => println!("Saved screenshot to {}", path.display()),
+		Err(e) => println!("failed to save screenshot to {}: {e}", path.display()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use replay::Record;
+	use std::time::Duration;
+
+	/// Records which ticks saw space pressed, and the input events it was given.
+	#[derive(Default)]
+	struct SpaceApp {
+		pressed: Vec<u64>,
+		events: Vec<input::InputEvent>,
+	}
+
+	impl App for SpaceApp {
+		fn init(&mut self, _init: Init<'_>) -> Result<(), RendererError> {
+			Ok(())
+		}
+
+		fn update(&mut self, update: Update<'_>) {
+			if update.input.key_pressed(input::KeyCode::Space) {
+				self.pressed.push(update.tick.index);
+			}
+		}
+
+		fn render(&mut self, _frame: Frame<'_>) -> Result<(), RendererError> {
+			Ok(())
+		}
+
+		fn input_event(&mut self, event: &input::InputEvent) {
+			self.events.push(event.clone());
+		}
+	}
+
+	fn engine(tick_rate: f64) -> Engine<SpaceApp, ecs::World> {
+		let loop_config = LoopConfig {
+			tick_rate,
+			..LoopConfig::default()
+		};
+		let audio = audio::Audio::new(audio::AudioOutput::Null);
+		let input = input::Input::new(input::ActionMap::default());
+		Engine::new(SpaceApp::default(), loop_config, audio, input).unwrap()
+	}
+
+	fn key(pressed: bool) -> Record {
+		Record::Event(input::InputEvent::Key {
+			key: input::KeyCode::Space,
+			pressed,
+		})
+	}
+
+	/// Space held during the second and third of four 10 ms ticks, over three frames.
+	fn records() -> Vec<Record> {
+		vec![
+			Record::Frame(Duration::from_millis(15)),
+			Record::Tick(0),
+			key(true),
+			Record::Frame(Duration::from_millis(15)),
+			Record::Tick(1),
+			Record::Tick(2),
+			key(false),
+			Record::Frame(Duration::from_millis(10)),
+			Record::Tick(3),
+		]
+	}
+
+	#[test]
+	fn replays_input_on_the_recorded_ticks() {
+		let recording = replay::Recording { records: records() };
+		let mut engine = engine(100.0);
+		let alpha = engine.replay(&recording).unwrap();
+		assert_eq!(engine.app.pressed, [1, 2]);
+		let events: Vec<_> = records()
+			.into_iter()
+			.filter_map(|record| match record {
+				Record::Event(event) => Some(event),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(engine.app.events, events);
+		assert_eq!(engine.elapsed, Duration::from_millis(40));
+		assert!(alpha.abs() < 1e-6);
+	}
+
+	#[test]
+	fn detects_diverging_replays() {
+		let recording = replay::Recording { records: records() };
+		assert!(matches!(
+			engine(50.0).replay(&recording),
+			Err(replay::ReplayError::Diverged { frame: 1, tick: 0 })
+		));
+
+		let mut skipped = records();
+		skipped[4] = Record::Tick(2);
+		assert!(matches!(
+			engine(100.0).replay(&replay::Recording { records: skipped }),
+			Err(replay::ReplayError::Diverged { frame: 2, tick: 2 })
+		));
+
+		let mut missing = records();
+		missing.pop();
+		assert!(matches!(
+			engine(100.0).replay(&replay::Recording { records: missing }),
+			Err(replay::ReplayError::Diverged { frame: 3, tick: 3 })
+		));
+	}
+}
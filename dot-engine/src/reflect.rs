@@ -0,0 +1,474 @@
+//! Loading SPIR-V modules at runtime and minimal reflection over their words, for what vulkano's
+//! entry point info doesn't expose. The renderer checks the render graph against the descriptor
+//! layouts vulkano reflects when creating the pipelines, in `graph::compile`.
+//!
+//! This module doesn't depend on the rest of the crate, as `build.rs` includes it to generate the
+//! shader manifest.
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+const OP_ENTRY_POINT: u16 = 15;
+const OP_EXECUTION_MODE: u16 = 16;
+const OP_TYPE_IMAGE: u16 = 25;
+const OP_TYPE_SAMPLER: u16 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+const OP_TYPE_ARRAY: u16 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+const OP_TYPE_STRUCT: u16 = 30;
+const OP_TYPE_POINTER: u16 = 32;
+const OP_VARIABLE: u16 = 59;
+const OP_DECORATE: u16 = 71;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+/// Why a SPIR-V module was rejected before handing it to Vulkan.
+#[derive(Debug)]
+pub enum SpirvError {
+	Io(std::io::Error),
+	/// The byte length isn't a multiple of the word size.
+	Length(usize),
+	/// The module is shorter than the SPIR-V header.
+	Truncated,
+	/// The first word isn't the SPIR-V magic number in either byte order.
+	Magic(u32),
+	/// The module's SPIR-V version, as (major, minor), is newer than the device accepts.
+	Version {
+		module: (u8, u8),
+		supported: (u8, u8),
+	},
+}
+
+impl std::fmt::Display for SpirvError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(_) => write!(f, "failed to read the module"),
+			Self::Length(length) => write!(f, "{length} bytes is not a whole number of words"),
+			Self::Truncated => write!(f, "the module is shorter than the SPIR-V header"),
+			Self::Magic(word) => write!(f, "{word:#010x} is not the SPIR-V magic number"),
+			Self::Version { module, supported } => write!(
+				f,
+				"SPIR-V {}.{} is newer than the device supports ({}.{})",
+				module.0, module.1, supported.0, supported.1,
+			),
+		}
+	}
+}
+
+impl std::error::Error for SpirvError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			Self::Length(_) | Self::Truncated | Self::Magic(_) | Self::Version { .. } => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for SpirvError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+/// Reads a SPIR-V module from `path`, e.g. from a shader pack. See [`from_bytes`].
+pub fn load(path: impl AsRef<std::path::Path>) -> Result<Vec<u32>, SpirvError> {
+	from_bytes(&std::fs::read(path)?)
+}
+
+/// Converts a SPIR-V module to words, checking its length and magic number. Modules in either
+/// byte order are accepted.
+pub fn from_bytes(bytes: &[u8]) -> Result<Vec<u32>, SpirvError> {
+	if !bytes.chunks_exact(4).remainder().is_empty() {
+		return Err(SpirvError::Length(bytes.len()));
+	}
+	if bytes.len() < HEADER_WORDS * 4 {
+		return Err(SpirvError::Truncated);
+	}
+	let mut words: Vec<u32> = bytes
+		.chunks_exact(4)
+		.map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+		.collect();
+	if words[0] == MAGIC.swap_bytes() {
+		words.iter_mut().for_each(|word| *word = word.swap_bytes());
+	} else if words[0] != MAGIC {
+		return Err(SpirvError::Magic(words[0]));
+	}
+	Ok(words)
+}
+
+/// The module's SPIR-V version as (major, minor).
+pub fn version(spirv: &[u32]) -> (u8, u8) {
+	let word = spirv.get(1).copied().unwrap_or(0);
+	((word >> 16) as u8, (word >> 8) as u8)
+}
+
+/// Checks the module's SPIR-V version against the newest one the device accepts, as
+/// (major, minor).
+pub fn check_version(spirv: &[u32], supported: (u8, u8)) -> Result<(), SpirvError> {
+	let module = version(spirv);
+	if module > supported {
+		return Err(SpirvError::Version { module, supported });
+	}
+	Ok(())
+}
+
+/// The shader stage an entry point runs in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+	Vertex,
+	Fragment,
+	Compute,
+	/// Any other SPIR-V execution model, by number.
+	Other(u32),
+}
+
+impl Stage {
+	fn from_execution_model(execution_model: u32) -> Self {
+		match execution_model {
+			0 => Self::Vertex,
+			4 => Self::Fragment,
+			5 => Self::Compute,
+			other => Self::Other(other),
+		}
+	}
+}
+
+/// An entry point declared by a module.
+pub struct EntryPoint {
+	pub name: String,
+	pub stage: Stage,
+	/// See [`local_size`]. Always `None` outside compute shaders.
+	pub local_size: Option<[u32; 3]>,
+}
+
+impl std::fmt::Display for EntryPoint {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match (self.stage, self.local_size) {
+			(Stage::Compute, Some([x, y, z])) => write!(f, "{} (compute, {x}x{y}x{z})", self.name),
+			(Stage::Compute, None) => write!(f, "{} (compute, specialized local size)", self.name),
+			(stage, _) => write!(f, "{} ({stage:?})", self.name),
+		}
+	}
+}
+
+/// Lists the entry points of a module in declaration order.
+pub fn entry_points(spirv: &[u32]) -> Vec<EntryPoint> {
+	instructions(spirv)
+		.filter(|(opcode, _)| *opcode == OP_ENTRY_POINT)
+		.filter_map(|(_, operands)| {
+			let name = literal_string(operands.get(2..)?).0;
+			Some(EntryPoint {
+				stage: Stage::from_execution_model(operands[0]),
+				local_size: local_size(spirv, &name),
+				name,
+			})
+		})
+		.collect()
+}
+
+/// The kind of resource a descriptor binding expects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DescriptorKind {
+	Sampler,
+	CombinedImageSampler,
+	SampledImage,
+	StorageImage,
+	UniformBuffer,
+	StorageBuffer,
+	/// Texel buffers, acceleration structures and anything else.
+	Other,
+}
+
+/// A resource variable decorated with a descriptor set and binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+	pub set: u32,
+	pub binding: u32,
+	/// The kind of a single element for arrays of resources.
+	pub kind: DescriptorKind,
+}
+
+/// Lists the descriptor bindings declared anywhere in a module, ordered by set and binding.
+/// Modules built one per entry point only contain the bindings that entry point uses.
+pub fn descriptor_bindings(spirv: &[u32]) -> Vec<DescriptorBinding> {
+	let mut sets = std::collections::HashMap::new();
+	let mut bindings = std::collections::HashMap::new();
+	let mut buffer_blocks = std::collections::HashSet::new();
+	let mut pointers = std::collections::HashMap::new();
+	let mut types = std::collections::HashMap::new();
+	let mut variables = Vec::new();
+	for (opcode, operands) in instructions(spirv) {
+		match (opcode, operands) {
+			(OP_DECORATE, &[target, DECORATION_DESCRIPTOR_SET, set]) => {
+				sets.insert(target, set);
+			},
+			(OP_DECORATE, &[target, DECORATION_BINDING, binding]) => {
+				bindings.insert(target, binding);
+			},
+			(OP_DECORATE, &[target, DECORATION_BUFFER_BLOCK]) => {
+				buffer_blocks.insert(target);
+			},
+			(OP_TYPE_POINTER, &[id, storage_class, pointee]) => {
+				pointers.insert(id, (storage_class, pointee));
+			},
+			(
+				OP_TYPE_IMAGE
+				| OP_TYPE_SAMPLER
+				| OP_TYPE_SAMPLED_IMAGE
+				| OP_TYPE_ARRAY
+				| OP_TYPE_RUNTIME_ARRAY
+				| OP_TYPE_STRUCT,
+				&[id, ..],
+			) => {
+				types.insert(id, (opcode, operands));
+			},
+			(OP_VARIABLE, &[result_type, id, ..]) => variables.push((id, result_type)),
+			_ => (),
+		}
+	}
+
+	let mut descriptor_bindings: Vec<_> = variables
+		.into_iter()
+		.filter_map(|(id, result_type)| {
+			let set = *sets.get(&id)?;
+			let binding = *bindings.get(&id)?;
+			let &(storage_class, mut pointee) = pointers.get(&result_type)?;
+
+			let kind = loop {
+				break match types.get(&pointee) {
+					Some((OP_TYPE_ARRAY | OP_TYPE_RUNTIME_ARRAY, &[_, element, ..])) => {
+						pointee = element;
+						continue;
+					},
+					Some((OP_TYPE_SAMPLER, _)) => DescriptorKind::Sampler,
+					Some((OP_TYPE_SAMPLED_IMAGE, _)) => DescriptorKind::CombinedImageSampler,
+					// Operands: id, sampled type, dim, depth, arrayed, multisampled, sampled.
+					Some((OP_TYPE_IMAGE, &[_, _, dim, _, _, _, sampled, ..])) if dim != 5 => {
+						match sampled {
+							2 => DescriptorKind::StorageImage,
+							_ => DescriptorKind::SampledImage,
+						}
+					},
+					Some((OP_TYPE_STRUCT, _)) => match storage_class {
+						STORAGE_CLASS_STORAGE_BUFFER => DescriptorKind::StorageBuffer,
+						STORAGE_CLASS_UNIFORM if buffer_blocks.contains(&pointee) => {
+							DescriptorKind::StorageBuffer
+						},
+						STORAGE_CLASS_UNIFORM => DescriptorKind::UniformBuffer,
+						_ => DescriptorKind::Other,
+					},
+					_ => DescriptorKind::Other,
+				};
+			};
+			Some(DescriptorBinding { set, binding, kind })
+		})
+		.collect();
+	descriptor_bindings.sort_by_key(|binding| (binding.set, binding.binding));
+	descriptor_bindings
+}
+
+/// SPIR-V modules looked up by the entry points they declare: a single module with every entry
+/// point, or one module per entry point as `build.rs` produces.
+#[derive(Clone, Default)]
+pub struct Modules {
+	modules: Vec<Vec<u32>>,
+}
+
+impl Modules {
+	pub fn single(spirv: Vec<u32>) -> Self {
+		Self {
+			modules: vec![spirv],
+		}
+	}
+
+	pub fn push(&mut self, spirv: Vec<u32>) {
+		self.modules.push(spirv);
+	}
+
+	/// The first module declaring `entry_point`.
+	pub fn find(&self, entry_point: &str) -> Option<&[u32]> {
+		self.modules
+			.iter()
+			.find(|spirv| entry_point_id(spirv, entry_point).is_some())
+			.map(Vec::as_slice)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &[u32]> {
+		self.modules.iter().map(Vec::as_slice)
+	}
+}
+
+/// Iterates over the instructions after the header as (opcode, operands).
+fn instructions(spirv: &[u32]) -> impl Iterator<Item = (u16, &[u32])> {
+	let mut words = if spirv.first() == Some(&MAGIC) {
+		spirv.get(HEADER_WORDS..).unwrap_or(&[])
+	} else {
+		&[]
+	};
+	std::iter::from_fn(move || {
+		let word_count = (*words.first()? >> 16) as usize;
+		if word_count == 0 || word_count > words.len() {
+			return None;
+		}
+		let (instruction, rest) = words.split_at(word_count);
+		words = rest;
+		Some((instruction[0] as u16, &instruction[1..]))
+	})
+}
+
+/// Decodes a nul-terminated SPIR-V literal string, returning it and the words after it.
+fn literal_string(operands: &[u32]) -> (String, &[u32]) {
+	let mut bytes = Vec::new();
+	for (i, word) in operands.iter().enumerate() {
+		for byte in word.to_le_bytes() {
+			if byte == 0 {
+				return (
+					String::from_utf8_lossy(&bytes).into_owned(),
+					&operands[i + 1..],
+				);
+			}
+			bytes.push(byte);
+		}
+	}
+	(String::from_utf8_lossy(&bytes).into_owned(), &[])
+}
+
+/// Returns the result id of the function declared as entry point `name`.
+fn entry_point_id(spirv: &[u32], name: &str) -> Option<u32> {
+	instructions(spirv)
+		.filter(|(opcode, _)| *opcode == OP_ENTRY_POINT)
+		.find_map(|(_, operands)| {
+			let (&id, rest) = operands.get(1..)?.split_first()?;
+			(literal_string(rest).0 == name).then_some(id)
+		})
+}
+
+/// The `LocalSize` execution mode of entry point `name`, i.e. its `threads(..)` attribute.
+/// Returns `None` if the entry point doesn't exist or its size is set through specialization
+/// constants.
+pub fn local_size(spirv: &[u32], name: &str) -> Option<[u32; 3]> {
+	let id = entry_point_id(spirv, name)?;
+	instructions(spirv)
+		.filter(|(opcode, _)| *opcode == OP_EXECUTION_MODE)
+		.find_map(|(_, operands)| match operands {
+			&[entry_point, EXECUTION_MODE_LOCAL_SIZE, x, y, z] if entry_point == id => {
+				Some([x, y, z])
+			},
+			_ => None,
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::shaders::EntryPoint;
+
+	fn main_spirv() -> Vec<u32> {
+		from_bytes(EntryPoint::Main.spirv()).unwrap()
+	}
+
+	fn to_bytes(words: &[u32], to_bytes: fn(u32) -> [u8; 4]) -> Vec<u8> {
+		words.iter().copied().flat_map(to_bytes).collect()
+	}
+
+	#[test]
+	fn accepts_both_byte_orders() {
+		let spirv = main_spirv();
+		assert_eq!(spirv[0], MAGIC);
+		let big_endian = to_bytes(&spirv, u32::to_be_bytes);
+		assert_eq!(from_bytes(&big_endian).unwrap(), spirv);
+		assert_eq!(
+			from_bytes(&to_bytes(&spirv, u32::to_le_bytes)).unwrap(),
+			spirv
+		);
+	}
+
+	#[test]
+	fn rejects_malformed_modules() {
+		let bytes = EntryPoint::Main.spirv();
+		assert!(matches!(
+			from_bytes(&bytes[..bytes.len() - 1]),
+			Err(SpirvError::Length(length)) if length == bytes.len() - 1
+		));
+		assert!(matches!(
+			from_bytes(&bytes[..HEADER_WORDS * 4 - 4]),
+			Err(SpirvError::Truncated)
+		));
+		assert!(matches!(from_bytes(&[]), Err(SpirvError::Truncated)));
+		let mut spirv = main_spirv();
+		spirv[0] = 0xdead_beef;
+		assert!(matches!(
+			from_bytes(&to_bytes(&spirv, u32::to_le_bytes)),
+			Err(SpirvError::Magic(0xdead_beef))
+		));
+	}
+
+	#[test]
+	fn rejects_newer_versions() {
+		let mut spirv = main_spirv();
+		spirv[1] = 0x0001_0600;
+		assert_eq!(version(&spirv), (1, 6));
+		assert!(check_version(&spirv, (1, 6)).is_ok());
+		assert!(check_version(&spirv, (2, 0)).is_ok());
+		assert!(matches!(
+			check_version(&spirv, (1, 5)),
+			Err(SpirvError::Version {
+				module: (1, 6),
+				supported: (1, 5),
+			})
+		));
+	}
+
+	#[test]
+	fn reflects_the_embedded_modules() {
+		let modules = crate::shaders::modules();
+		for &entry_point in EntryPoint::ALL {
+			let spirv = modules.find(entry_point.name()).unwrap();
+			let declared = entry_points(spirv);
+			assert_eq!(declared.len(), 1);
+			assert_eq!(declared[0].name, entry_point.name());
+			assert_eq!(declared[0].stage, Stage::Compute);
+			assert_eq!(declared[0].local_size, entry_point.local_size());
+			assert_eq!(descriptor_bindings(spirv), entry_point.bindings());
+		}
+
+		let main = modules.find("main").unwrap();
+		assert_eq!(local_size(main, "main"), Some([8, 8, 1]));
+		assert_eq!(local_size(main, "missing"), None);
+		let main_1x1 = modules.find("main_1x1").unwrap();
+		assert_eq!(local_size(main_1x1, "main_1x1"), Some([1, 1, 1]));
+		assert_eq!(
+			descriptor_bindings(main),
+			[
+				DescriptorBinding {
+					set: 0,
+					binding: 0,
+					kind: DescriptorKind::StorageImage,
+				},
+				DescriptorBinding {
+					set: 0,
+					binding: 1,
+					kind: DescriptorKind::StorageBuffer,
+				},
+			],
+		);
+		let sprites = modules.find("sprites").unwrap();
+		assert_eq!(
+			descriptor_bindings(sprites)
+				.iter()
+				.map(|binding| binding.kind)
+				.collect::<Vec<_>>(),
+			[
+				DescriptorKind::StorageImage,
+				DescriptorKind::StorageBuffer,
+				DescriptorKind::StorageImage,
+				DescriptorKind::StorageBuffer,
+			],
+		);
+	}
+}
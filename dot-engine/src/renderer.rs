@@ -0,0 +1,1199 @@
+//! The compute renderer: creates the device, runs the render graph's passes each frame and
+//! presents to a window or renders offscreen.
+
+use crate::{
+	device, ecs, graph, reflect, screenshot,
+	window::{self, PresentMode},
+	DeviceSelection, RendererError,
+};
+use vulkano::{pipeline::Pipeline, sync::GpuFuture};
+
+/// Where the compute pipeline writes its output.
+enum RenderTarget {
+	/// Presenting to a window surface.
+	Swapchain {
+		swapchain: std::sync::Arc<vulkano::swapchain::Swapchain>,
+		images: Vec<std::sync::Arc<vulkano::image::Image>>,
+		presentation: Presentation,
+		/// The image written with [`Presentation::Intermediate`], of the swapchain's size.
+		intermediate: Option<std::sync::Arc<vulkano::image::Image>>,
+	},
+	/// Rendering into a single storage image without any surface, e.g. on lavapipe in CI.
+	Offscreen {
+		image: std::sync::Arc<vulkano::image::Image>,
+	},
+}
+
+/// How frames get onto the swapchain images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Presentation {
+	/// The passes write to the swapchain image.
+	Direct,
+	/// The swapchain format can't be written as a storage image, so the passes write to an
+	/// intermediate image of `format`, which is then transferred to the swapchain image.
+	Intermediate {
+		format: vulkano::format::Format,
+		transfer: Transfer,
+	},
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transfer {
+	/// Bit for bit, from an image of a format with the same layout.
+	Copy,
+	/// Converting the values to the swapchain format.
+	Blit,
+}
+
+/// Swapchain formats tried for each color space, best first. UNORM formats come before sRGB
+/// ones: shaders write sRGB-encoded values, which those store as they are.
+const SRGB_FORMATS: &[vulkano::format::Format] = &[
+	vulkano::format::Format::B8G8R8A8_UNORM,
+	vulkano::format::Format::R8G8B8A8_UNORM,
+	vulkano::format::Format::A2B10G10R10_UNORM_PACK32,
+	vulkano::format::Format::A2R10G10B10_UNORM_PACK32,
+	vulkano::format::Format::B8G8R8A8_SRGB,
+	vulkano::format::Format::R8G8B8A8_SRGB,
+];
+const HDR10_FORMATS: &[vulkano::format::Format] = &[
+	vulkano::format::Format::A2B10G10R10_UNORM_PACK32,
+	vulkano::format::Format::A2R10G10B10_UNORM_PACK32,
+	vulkano::format::Format::R16G16B16A16_SFLOAT,
+];
+const SCRGB_FORMATS: &[vulkano::format::Format] = &[vulkano::format::Format::R16G16B16A16_SFLOAT];
+
+/// Intermediate format blitted to swapchain formats with more than 8 bits per channel, which,
+/// like [`OFFSCREEN_FORMAT`], is guaranteed to support storage writes.
+const WIDE_INTERMEDIATE_FORMAT: vulkano::format::Format =
+	vulkano::format::Format::R16G16B16A16_SFLOAT;
+
+/// Runs the passes of a [`graph::RenderGraph`] each frame, presenting to a window or rendering
+/// into an offscreen image.
+pub struct Renderer {
+	device: std::sync::Arc<vulkano::device::Device>,
+	/// Queue the compute pipeline runs on.
+	queue: std::sync::Arc<vulkano::device::Queue>,
+	/// Queue frames are presented on; may belong to a different family than `queue`.
+	present_queue: std::sync::Arc<vulkano::device::Queue>,
+	target: RenderTarget,
+	/// Modules the render graph's entry points are looked up in.
+	shaders: reflect::Modules,
+	/// The render graph's passes, in execution order.
+	passes: Vec<graph::CompiledPass>,
+	/// Transient images of the render graph by name, all of the size of the last frame.
+	transient_images: std::collections::HashMap<String, std::sync::Arc<vulkano::image::Image>>,
+	/// Data for the render graph's extracted buffers, uploaded with every frame.
+	extract: ecs::Extract,
+	/// Requested present mode, applied with fallbacks whenever the swapchain is (re)created.
+	present_mode: PresentMode,
+	recreate_swapchain: bool,
+	previous_frame_end: Option<Box<dyn GpuFuture>>,
+	memory_allocator: std::sync::Arc<
+		vulkano::memory::allocator::GenericMemoryAllocator<
+			vulkano::memory::allocator::FreeListAllocator,
+		>,
+	>,
+	descriptor_set_allocator: vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator,
+	command_buffer_allocator: vulkano::command_buffer::allocator::StandardCommandBufferAllocator,
+	buffer_allocator: vulkano::buffer::allocator::SubbufferAllocator,
+	capture_requested: bool,
+	captured_frame: Option<screenshot::Frame>,
+	start_time: std::time::Instant,
+	last_frame_time: std::time::Instant,
+	/// Time and delta time of the next frame, if given instead of measured.
+	frame_clock: Option<(std::time::Duration, std::time::Duration)>,
+	frame: u32,
+	mouse_position: [f32; 2],
+}
+
+/// A logical device with its compute queue and its present queue.
+type DeviceQueues = (
+	std::sync::Arc<vulkano::device::Device>,
+	std::sync::Arc<vulkano::device::Queue>,
+	std::sync::Arc<vulkano::device::Queue>,
+);
+
+/// Host-visible destination of a frame copy recorded alongside the dispatch.
+struct PendingCapture {
+	buffer: vulkano::buffer::Subbuffer<[u8]>,
+	extent: [u32; 2],
+	format: vulkano::format::Format,
+}
+
+/// Format of the offscreen target; `R8G8B8A8_UNORM` is guaranteed to support storage writes.
+const OFFSCREEN_FORMAT: vulkano::format::Format = vulkano::format::Format::R8G8B8A8_UNORM;
+
+/// Device extensions needed by every renderer; windowed ones add `khr_swapchain`.
+const HEADLESS_DEVICE_EXTENSIONS: vulkano::device::DeviceExtensions =
+	vulkano::device::DeviceExtensions {
+		khr_storage_buffer_storage_class: true,
+		khr_vulkan_memory_model: true,
+		..vulkano::device::DeviceExtensions::empty()
+	};
+
+impl Renderer {
+	pub fn new(
+		window: std::sync::Arc<winit::window::Window>,
+		required_extensions: vulkano::instance::InstanceExtensions,
+		app_version: vulkano::Version,
+		shaders: &reflect::Modules,
+		graph: &graph::RenderGraph,
+		device_selection: &DeviceSelection,
+		surface_config: &window::SurfaceConfig,
+	) -> Result<Self, RendererError> {
+		let instance = Self::create_instance(
+			required_extensions,
+			vulkano::instance::InstanceExtensions {
+				ext_swapchain_colorspace: true,
+				..vulkano::instance::InstanceExtensions::empty()
+			},
+			app_version,
+		)?;
+
+		let surface = vulkano::swapchain::Surface::from_window(instance.clone(), window.clone())?;
+
+		let device_extensions = vulkano::device::DeviceExtensions {
+			khr_swapchain: true,
+			..HEADLESS_DEVICE_EXTENSIONS
+		};
+		let (device, queue, present_queue) = Self::create_device(
+			&instance,
+			device_extensions,
+			Some(&surface),
+			device_selection,
+		)?;
+
+		let (swapchain, images, presentation) = {
+			let surface_capabilities = device
+				.physical_device()
+				.surface_capabilities(&surface, Default::default())
+				.map_err(RendererError::Swapchain)?;
+
+			let (image_format, image_color_space, presentation) = Self::choose_surface_format(
+				&device,
+				&surface,
+				&surface_capabilities,
+				surface_config,
+				supports_blit(&queue) || supports_blit(&present_queue),
+			)?;
+			println!(
+				"Using surface format {image_format:?} in {image_color_space:?}{}",
+				match presentation {
+					Presentation::Direct => String::new(),
+					Presentation::Intermediate { format, transfer } => {
+						format!(" via an intermediate {format:?} image ({transfer:?})")
+					},
+				}
+			);
+
+			let image_usage = match presentation {
+				// Transfers are only needed for screenshots, so don't require them.
+				Presentation::Direct => {
+					vulkano::image::ImageUsage::STORAGE
+						| (surface_capabilities.supported_usage_flags
+							& vulkano::image::ImageUsage::TRANSFER_SRC)
+				},
+				// Screenshots read the intermediate image.
+				Presentation::Intermediate { .. } => vulkano::image::ImageUsage::TRANSFER_DST,
+			};
+
+			let image_sharing = queue_sharing(&queue, &present_queue);
+
+			let (present_mode, min_image_count) = Self::choose_present_mode(
+				&device,
+				&surface,
+				&surface_capabilities,
+				surface_config.present_mode,
+			)?;
+
+			let (swapchain, images) = vulkano::swapchain::Swapchain::new(
+				device.clone(),
+				surface,
+				vulkano::swapchain::SwapchainCreateInfo {
+					min_image_count,
+
+					present_mode,
+
+					image_format,
+
+					image_color_space,
+
+					image_extent: window.inner_size().into(),
+
+					image_usage,
+
+					image_sharing,
+
+					composite_alpha: surface_config.composite_alpha,
+
+					..Default::default()
+				},
+			)
+			.map_err(RendererError::Swapchain)?;
+			(swapchain, images, presentation)
+		};
+
+		let mut renderer = Self::from_parts(device, queue, present_queue, shaders, graph, |_| {
+			Ok(RenderTarget::Swapchain {
+				swapchain,
+				images,
+				presentation,
+				intermediate: None,
+			})
+		})?;
+		renderer.present_mode = surface_config.present_mode;
+		Ok(renderer)
+	}
+
+	/// The first of `preferred` and its fallbacks that `surface` supports, and the number of
+	/// images to ask for with it: one more for mailbox, so there is always an image to render to
+	/// while one is shown and another waits.
+	fn choose_present_mode(
+		device: &vulkano::device::Device,
+		surface: &vulkano::swapchain::Surface,
+		surface_capabilities: &vulkano::swapchain::SurfaceCapabilities,
+		preferred: PresentMode,
+	) -> Result<(vulkano::swapchain::PresentMode, u32), RendererError> {
+		let supported = device
+			.physical_device()
+			.surface_present_modes(surface, Default::default())
+			.map_err(RendererError::Swapchain)?
+			.collect::<Vec<_>>();
+		let present_mode = preferred
+			.fallbacks()
+			.iter()
+			.map(|mode| match mode {
+				PresentMode::Fifo => vulkano::swapchain::PresentMode::Fifo,
+				PresentMode::Mailbox => vulkano::swapchain::PresentMode::Mailbox,
+				PresentMode::Immediate => vulkano::swapchain::PresentMode::Immediate,
+			})
+			.find(|mode| supported.contains(mode))
+			.unwrap_or(vulkano::swapchain::PresentMode::Fifo);
+		let image_count = match present_mode {
+			vulkano::swapchain::PresentMode::Mailbox => 3,
+			_ => 2,
+		};
+		let mut min_image_count = surface_capabilities.min_image_count.max(image_count);
+		if let Some(max_image_count) = surface_capabilities.max_image_count {
+			min_image_count = min_image_count.min(max_image_count);
+		}
+		Ok((present_mode, min_image_count))
+	}
+
+	/// The best format and color space `surface` supports for `surface_config`, and how frames
+	/// get onto images of that format. Formats the passes can write directly beat those needing an
+	/// intermediate image, but a format tried earlier beats both: the explicitly configured one
+	/// first, then those of the configured color space, then those of sRGB, then anything else.
+	/// Formats needing a blit are skipped unless `can_blit`, as only graphics queues can blit.
+	fn choose_surface_format(
+		device: &vulkano::device::Device,
+		surface: &vulkano::swapchain::Surface,
+		surface_capabilities: &vulkano::swapchain::SurfaceCapabilities,
+		surface_config: &window::SurfaceConfig,
+		can_blit: bool,
+	) -> Result<
+		(
+			vulkano::format::Format,
+			vulkano::swapchain::ColorSpace,
+			Presentation,
+		),
+		RendererError,
+	> {
+		let physical_device = device.physical_device();
+		let supported = physical_device
+			.surface_formats(surface, Default::default())
+			.map_err(RendererError::Swapchain)?;
+		let features = |format| {
+			physical_device
+				.format_properties(format)
+				.map(|properties| properties.optimal_tiling_features)
+				.unwrap_or_default()
+		};
+		let usage = surface_capabilities.supported_usage_flags;
+		let presentation_for = |format: vulkano::format::Format| {
+			use vulkano::format::FormatFeatures;
+			if usage.intersects(vulkano::image::ImageUsage::STORAGE)
+				&& features(format).intersects(FormatFeatures::STORAGE_IMAGE)
+			{
+				return Some(Presentation::Direct);
+			}
+			if !usage.intersects(vulkano::image::ImageUsage::TRANSFER_DST) {
+				return None;
+			}
+			// A blit would encode the already encoded values again, so sRGB formats are copied
+			// from their UNORM counterparts.
+			if let Some(unorm) = unorm_counterpart(format) {
+				return features(unorm)
+					.intersects(FormatFeatures::STORAGE_IMAGE)
+					.then_some(Presentation::Intermediate {
+						format: unorm,
+						transfer: Transfer::Copy,
+					});
+			}
+			let intermediate = if format.components()[0] > 8 {
+				WIDE_INTERMEDIATE_FORMAT
+			} else {
+				OFFSCREEN_FORMAT
+			};
+			(can_blit && features(format).intersects(FormatFeatures::BLIT_DST)).then_some(
+				Presentation::Intermediate {
+					format: intermediate,
+					transfer: Transfer::Blit,
+				},
+			)
+		};
+
+		let color_space = surface_config.color_space.to_vulkan();
+		let preferred = match surface_config.color_space {
+			window::ColorSpace::Srgb => SRGB_FORMATS,
+			window::ColorSpace::Hdr10 => HDR10_FORMATS,
+			window::ColorSpace::ScRgb => SCRGB_FORMATS,
+		};
+		let srgb = vulkano::swapchain::ColorSpace::SrgbNonLinear;
+		let candidates = surface_config
+			.format
+			.map(|format| (format, color_space))
+			.into_iter()
+			.chain(surface_config.format.map(|format| (format, srgb)))
+			.map(|candidate| (0, candidate))
+			.chain(preferred.iter().map(|&format| (1, (format, color_space))))
+			.chain(SRGB_FORMATS.iter().map(|&format| (2, (format, srgb))))
+			.chain(supported.iter().map(|&candidate| (3, candidate)));
+		candidates
+			.enumerate()
+			.filter(|(_, (_, candidate))| supported.contains(candidate))
+			.filter_map(|(index, (tier, (format, color_space)))| {
+				let presentation = presentation_for(format)?;
+				let key = (tier, presentation != Presentation::Direct, index);
+				Some((key, (format, color_space, presentation)))
+			})
+			.min_by_key(|(key, _)| *key)
+			.map(|(_, chosen)| chosen)
+			.ok_or(RendererError::NoSurfaceFormat(supported))
+	}
+
+	/// Creates a renderer without a window, drawing into an offscreen storage image of
+	/// `image_extent` with `graph`. Only a compute-capable queue is required, so software
+	/// implementations such as lavapipe work.
+	pub fn new_headless(
+		image_extent: [u32; 2],
+		app_version: vulkano::Version,
+		shaders: &reflect::Modules,
+		graph: &graph::RenderGraph,
+		device_selection: &DeviceSelection,
+	) -> Result<Self, RendererError> {
+		let instance = Self::create_instance(
+			vulkano::instance::InstanceExtensions::empty(),
+			vulkano::instance::InstanceExtensions::empty(),
+			app_version,
+		)?;
+
+		let (device, queue, present_queue) = Self::create_device(
+			&instance,
+			HEADLESS_DEVICE_EXTENSIONS,
+			None,
+			device_selection,
+		)?;
+		// Without a surface both queues come from one family, so this is exclusive.
+		let sharing = queue_sharing(&queue, &present_queue);
+
+		Self::from_parts(
+			device,
+			queue,
+			present_queue,
+			shaders,
+			graph,
+			|memory_allocator| {
+				let image = vulkano::image::Image::new(
+					memory_allocator.clone(),
+					vulkano::image::ImageCreateInfo {
+						image_type: vulkano::image::ImageType::Dim2d,
+						format: OFFSCREEN_FORMAT,
+						extent: [image_extent[0], image_extent[1], 1],
+						usage: vulkano::image::ImageUsage::STORAGE
+							| vulkano::image::ImageUsage::TRANSFER_SRC,
+						sharing,
+						..Default::default()
+					},
+					vulkano::memory::allocator::AllocationCreateInfo {
+						memory_type_filter:
+							vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE,
+						..Default::default()
+					},
+				)?;
+				Ok(RenderTarget::Offscreen { image })
+			},
+		)
+	}
+
+	/// Creates an instance with `required_extensions` and those of `optional_extensions` that the
+	/// Vulkan library supports.
+	fn create_instance(
+		required_extensions: vulkano::instance::InstanceExtensions,
+		optional_extensions: vulkano::instance::InstanceExtensions,
+		app_version: vulkano::Version,
+	) -> Result<std::sync::Arc<vulkano::instance::Instance>, RendererError> {
+		let library = vulkano::VulkanLibrary::new()?;
+		let enabled_extensions =
+			required_extensions | optional_extensions.intersection(library.supported_extensions());
+		let instance = vulkano::instance::Instance::new(
+			library,
+			vulkano::instance::InstanceCreateInfo {
+				application_version: app_version,
+				engine_version: vulkano::Version::major_minor(0, 1),
+				engine_name: Some("Dot".to_owned()),
+				flags: vulkano::instance::InstanceCreateFlags::ENUMERATE_PORTABILITY,
+				enabled_extensions,
+				..Default::default()
+			},
+		)?;
+		Ok(instance)
+	}
+
+	/// Lists every physical device and whether a headless renderer would use it under
+	/// `device_selection`, or why not.
+	pub fn list_devices(
+		app_version: vulkano::Version,
+		device_selection: &DeviceSelection,
+	) -> Result<Vec<device::DeviceReport>, RendererError> {
+		let instance = Self::create_instance(
+			vulkano::instance::InstanceExtensions::empty(),
+			vulkano::instance::InstanceExtensions::empty(),
+			app_version,
+		)?;
+		device::report_devices(
+			&instance,
+			HEADLESS_DEVICE_EXTENSIONS,
+			None,
+			device_selection,
+		)
+	}
+
+	/// Picks a physical device according to `device_selection` and creates a logical device
+	/// with a compute queue and a present queue. The two are the same queue unless the device
+	/// has a dedicated compute family or its compute family can't present to `surface`.
+	fn create_device(
+		instance: &std::sync::Arc<vulkano::instance::Instance>,
+		device_extensions: vulkano::device::DeviceExtensions,
+		surface: Option<&vulkano::swapchain::Surface>,
+		device_selection: &DeviceSelection,
+	) -> Result<DeviceQueues, RendererError> {
+		let (physical_device, queue_families) =
+			device::select_device(instance, device_extensions, surface, device_selection)?;
+
+		println!(
+			"Using device: {} (type: {:?}, compute queue family {}, present queue family {})",
+			physical_device.properties().device_name,
+			physical_device.properties().device_type,
+			queue_families.compute,
+			queue_families.present,
+		);
+
+		let mut queue_family_indices = vec![queue_families.compute];
+		if queue_families.present != queue_families.compute {
+			queue_family_indices.push(queue_families.present);
+		}
+
+		let (device, mut queues) = vulkano::device::Device::new(
+			physical_device,
+			vulkano::device::DeviceCreateInfo {
+				enabled_extensions: device_extensions,
+				queue_create_infos: queue_family_indices
+					.iter()
+					.map(|&queue_family_index| vulkano::device::QueueCreateInfo {
+						queue_family_index,
+						..Default::default()
+					})
+					.collect(),
+				enabled_features: vulkano::device::Features {
+					vulkan_memory_model: true,
+					..vulkano::device::Features::empty()
+				},
+				..Default::default()
+			},
+		)?;
+
+		let queue = queues.next().unwrap();
+		let present_queue = queues.next().unwrap_or_else(|| queue.clone());
+
+		Ok((device, queue, present_queue))
+	}
+
+	/// Builds the pipelines and allocators shared by both windowed and headless renderers.
+	/// `create_target` runs once the memory allocator exists.
+	fn from_parts(
+		device: std::sync::Arc<vulkano::device::Device>,
+		queue: std::sync::Arc<vulkano::device::Queue>,
+		present_queue: std::sync::Arc<vulkano::device::Queue>,
+		shaders: &reflect::Modules,
+		graph: &graph::RenderGraph,
+		create_target: impl FnOnce(
+			&std::sync::Arc<vulkano::memory::allocator::StandardMemoryAllocator>,
+		) -> Result<RenderTarget, RendererError>,
+	) -> Result<Self, RendererError> {
+		let passes = graph::compile(&device, shaders, graph)?;
+
+		let recreate_swapchain = false;
+
+		let previous_frame_end = Some(vulkano::sync::now(device.clone()).boxed());
+
+		let memory_allocator = std::sync::Arc::new(
+			vulkano::memory::allocator::StandardMemoryAllocator::new_default(device.clone()),
+		);
+		let descriptor_set_allocator =
+			vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator::new(
+				device.clone(),
+				Default::default(),
+			);
+		let command_buffer_allocator =
+			vulkano::command_buffer::allocator::StandardCommandBufferAllocator::new(
+				device.clone(),
+				Default::default(),
+			);
+		// Per-frame buffers are only read by dispatches on `queue`, and the capture buffer is
+		// only written there and then read on the host, so they stay exclusive to its family.
+		let buffer_allocator = vulkano::buffer::allocator::SubbufferAllocator::new(
+			memory_allocator.clone(),
+			vulkano::buffer::allocator::SubbufferAllocatorCreateInfo {
+				buffer_usage: vulkano::buffer::BufferUsage::STORAGE_BUFFER,
+				memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE
+					| vulkano::memory::allocator::MemoryTypeFilter::HOST_RANDOM_ACCESS,
+				..Default::default()
+			},
+		);
+		let target = create_target(&memory_allocator)?;
+		Ok(Self {
+			device,
+			queue,
+			present_queue,
+			target,
+			shaders: shaders.clone(),
+			passes,
+			transient_images: Default::default(),
+			extract: Default::default(),
+			present_mode: PresentMode::default(),
+			recreate_swapchain,
+			previous_frame_end,
+			memory_allocator,
+			descriptor_set_allocator,
+			command_buffer_allocator,
+			buffer_allocator,
+			capture_requested: false,
+			captured_frame: None,
+			start_time: std::time::Instant::now(),
+			last_frame_time: std::time::Instant::now(),
+			frame_clock: None,
+			frame: 0,
+			mouse_position: [0.0; 2],
+		})
+	}
+
+	pub fn run(
+		&mut self,
+		image_extent: [u32; 2],
+		additional_set: Option<std::sync::Arc<vulkano::descriptor_set::PersistentDescriptorSet>>,
+	) -> Result<(), RendererError> {
+		let RenderTarget::Swapchain {
+			swapchain,
+			images,
+			presentation,
+			intermediate,
+		} = &mut self.target
+		else {
+			return self.render_offscreen(additional_set);
+		};
+
+		if let Some(previous_frame_end) = self.previous_frame_end.as_mut() {
+			previous_frame_end.cleanup_finished();
+		}
+
+		if self.recreate_swapchain {
+			let surface = swapchain.surface();
+			let surface_capabilities = self
+				.device
+				.physical_device()
+				.surface_capabilities(surface, Default::default())
+				.map_err(RendererError::Swapchain)?;
+			let (present_mode, min_image_count) = Self::choose_present_mode(
+				&self.device,
+				surface,
+				&surface_capabilities,
+				self.present_mode,
+			)?;
+			let (new_swapchain, new_images) = swapchain
+				.recreate(vulkano::swapchain::SwapchainCreateInfo {
+					image_extent,
+					present_mode,
+					min_image_count,
+					..swapchain.create_info()
+				})
+				.map_err(RendererError::Swapchain)?;
+			*images = new_images;
+			*swapchain = new_swapchain;
+
+			self.recreate_swapchain = false;
+		}
+
+		let (image_index, suboptimal, acquire_future) =
+			match vulkano::swapchain::acquire_next_image(swapchain.clone(), None) {
+				Ok(r) => r,
+				Err(vulkano::Validated::Error(vulkano::VulkanError::OutOfDate)) => {
+					self.recreate_swapchain = true;
+					return Ok(());
+				},
+				Err(e) => return Err(RendererError::AcquireImage(e)),
+			};
+
+		if suboptimal {
+			self.recreate_swapchain = true;
+		}
+
+		let swapchain = swapchain.clone();
+		let swapchain_image = images[image_index as usize].clone();
+		let (image, transfer) = match *presentation {
+			Presentation::Direct => (swapchain_image, None),
+			Presentation::Intermediate { format, transfer } => {
+				let extent = swapchain_image.extent();
+				let image = match intermediate {
+					Some(image) if image.extent() == extent => image.clone(),
+					_ => {
+						let image = vulkano::image::Image::new(
+							self.memory_allocator.clone(),
+							vulkano::image::ImageCreateInfo {
+								image_type: vulkano::image::ImageType::Dim2d,
+								format,
+								extent,
+								usage: vulkano::image::ImageUsage::STORAGE
+									| vulkano::image::ImageUsage::TRANSFER_SRC,
+								sharing: queue_sharing(&self.queue, &self.present_queue),
+								..Default::default()
+							},
+							vulkano::memory::allocator::AllocationCreateInfo {
+								memory_type_filter:
+									vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE,
+								..Default::default()
+							},
+						)?;
+						*intermediate = Some(image.clone());
+						image
+					},
+				};
+				(image, Some((transfer, swapchain_image)))
+			},
+		};
+		// A screenshot that can't be taken is skipped rather than failing the frame.
+		let capture = self.prepare_capture(&image).unwrap_or_else(|e| {
+			println!("failed to take screenshot: {}", crate::report(&e));
+			None
+		});
+		self.prepare_transient_images(image_extent)?;
+		let params = self.next_frame_params(image_extent);
+		// Blits need a graphics queue; if the compute family has none, the present queue blits.
+		let (transfer, present_blit) = match transfer {
+			Some((Transfer::Blit, swapchain_image)) if !supports_blit(&self.queue) => {
+				let blit = self.record_present_blit(image.clone(), swapchain_image)?;
+				(None, Some(blit))
+			},
+			transfer => (transfer, None),
+		};
+		let command_buffer =
+			self.record_dispatch(image, params, additional_set, capture.as_ref(), transfer)?;
+
+		// A failed frame leaves `previous_frame_end` empty; the next one then starts afresh.
+		let future = self
+			.previous_frame_end
+			.take()
+			.unwrap_or_else(|| vulkano::sync::now(self.device.clone()).boxed())
+			.join(acquire_future)
+			.then_execute(self.queue.clone(), command_buffer)?;
+
+		// Presenting from another queue has to wait on a semaphore signalled by the dispatch.
+		let future = if self.queue.queue_family_index() == self.present_queue.queue_family_index() {
+			future.boxed()
+		} else {
+			future.then_signal_semaphore_and_flush()?.boxed()
+		};
+		// The intermediate image is shared concurrently, so the blit needs no ownership transfer.
+		let future = match present_blit {
+			Some(command_buffer) => future
+				.then_execute(self.present_queue.clone(), command_buffer)?
+				.boxed(),
+			None => future,
+		};
+
+		let future = future
+			.then_swapchain_present(
+				self.present_queue.clone(),
+				vulkano::swapchain::SwapchainPresentInfo::swapchain_image_index(
+					swapchain,
+					image_index,
+				),
+			)
+			.then_signal_fence_and_flush();
+
+		match future {
+			Ok(future) => {
+				if let Some(capture) = capture {
+					self.finish_capture(&future, capture)?;
+				}
+				self.previous_frame_end = Some(future.boxed());
+			},
+			Err(vulkano::Validated::Error(vulkano::VulkanError::OutOfDate)) => {
+				self.recreate_swapchain = true;
+			},
+			Err(e) => return Err(e.into()),
+		}
+		Ok(())
+	}
+
+	/// Renders one frame into the offscreen image. Does nothing for a windowed renderer.
+	pub fn render_offscreen(
+		&mut self,
+		additional_set: Option<std::sync::Arc<vulkano::descriptor_set::PersistentDescriptorSet>>,
+	) -> Result<(), RendererError> {
+		let RenderTarget::Offscreen { image } = &self.target else {
+			return Ok(());
+		};
+
+		if let Some(previous_frame_end) = self.previous_frame_end.as_mut() {
+			previous_frame_end.cleanup_finished();
+		}
+
+		let image = image.clone();
+		let image_extent = [image.extent()[0], image.extent()[1]];
+		let capture = self.prepare_capture(&image)?;
+		self.prepare_transient_images(image_extent)?;
+		let params = self.next_frame_params(image_extent);
+		let command_buffer =
+			self.record_dispatch(image, params, additional_set, capture.as_ref(), None)?;
+
+		let future = self
+			.previous_frame_end
+			.take()
+			.unwrap_or_else(|| vulkano::sync::now(self.device.clone()).boxed())
+			.then_execute(self.queue.clone(), command_buffer)?
+			.then_signal_fence_and_flush()?;
+
+		if let Some(capture) = capture {
+			self.finish_capture(&future, capture)?;
+		}
+		self.previous_frame_end = Some(future.boxed());
+		Ok(())
+	}
+
+	/// Advances the frame clock and returns the parameters for the frame about to be rendered.
+	fn next_frame_params(&mut self, image_extent: [u32; 2]) -> shader::FrameParams {
+		let now = std::time::Instant::now();
+		let (time, delta_time) = self
+			.frame_clock
+			.take()
+			.unwrap_or((now - self.start_time, now - self.last_frame_time));
+		let params = shader::FrameParams {
+			resolution: image_extent.into(),
+			mouse: self.mouse_position.into(),
+			time: time.as_secs_f32(),
+			delta_time: delta_time.as_secs_f32(),
+			frame: self.frame,
+			_padding: 0,
+		};
+		self.last_frame_time = now;
+		self.frame = self.frame.wrapping_add(1);
+		params
+	}
+
+	/// Sets [`shader::FrameParams::time`] and [`shader::FrameParams::delta_time`] for the next
+	/// frame instead of measuring them since the renderer was created and the previous frame.
+	pub fn set_frame_clock(&mut self, time: std::time::Duration, delta_time: std::time::Duration) {
+		self.frame_clock = Some((time, delta_time));
+	}
+
+	/// Sets the cursor position passed to the shader in [`shader::FrameParams::mouse`].
+	pub fn set_mouse_position(&mut self, position: [f32; 2]) {
+		self.mouse_position = position;
+	}
+
+	/// Replaces the render graph, compiling it against the renderer's shader modules. On failure
+	/// the current graph is kept.
+	pub fn set_graph(&mut self, graph: &graph::RenderGraph) -> Result<(), RendererError> {
+		self.passes = graph::compile(&self.device, &self.shaders, graph)?;
+		Ok(())
+	}
+
+	/// Sets the data extracted from the world for the next frames.
+	pub fn set_extract(&mut self, extract: ecs::Extract) {
+		self.extract = extract;
+	}
+
+	/// Rebuilds the render graph's pipelines from `shaders`, which replace the renderer's modules.
+	/// On failure the current pipelines and modules are kept.
+	#[cfg(feature = "hot-reload")]
+	pub(crate) fn reload_shader(
+		&mut self,
+		shaders: &reflect::Modules,
+	) -> Result<(), RendererError> {
+		let graph = graph::RenderGraph {
+			passes: self
+				.passes
+				.iter()
+				.map(|compiled| compiled.pass.clone())
+				.collect(),
+		};
+		self.passes = graph::compile(&self.device, shaders, &graph)?;
+		self.shaders = shaders.clone();
+		Ok(())
+	}
+
+	/// Allocates the render graph's transient images at `image_extent`, keeping those that
+	/// already have that size.
+	fn prepare_transient_images(&mut self, image_extent: [u32; 2]) -> Result<(), RendererError> {
+		let extent = [image_extent[0], image_extent[1], 1];
+		for name in self
+			.passes
+			.iter()
+			.flat_map(|compiled| compiled.pass.transient_images())
+		{
+			if self
+				.transient_images
+				.get(name)
+				.is_some_and(|image| image.extent() == extent)
+			{
+				continue;
+			}
+			let image = vulkano::image::Image::new(
+				self.memory_allocator.clone(),
+				vulkano::image::ImageCreateInfo {
+					image_type: vulkano::image::ImageType::Dim2d,
+					format: OFFSCREEN_FORMAT,
+					extent,
+					usage: vulkano::image::ImageUsage::STORAGE,
+					sharing: queue_sharing(&self.queue, &self.present_queue),
+					..Default::default()
+				},
+				vulkano::memory::allocator::AllocationCreateInfo {
+					memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_DEVICE,
+					..Default::default()
+				},
+			)?;
+			self.transient_images.insert(name.to_owned(), image);
+		}
+		Ok(())
+	}
+
+	/// Records a command buffer running every pass of the render graph with `image` as the
+	/// target, followed by a copy into `capture` and the transfer to a swapchain image if given.
+	/// `params` are bound at binding 1 of passes whose shader uses it, and the extracted buffers
+	/// are uploaded once for all passes. Each pass dispatches enough workgroups to cover the
+	/// image; shaders skip invocations outside it.
+	fn record_dispatch(
+		&self,
+		image: std::sync::Arc<vulkano::image::Image>,
+		params: shader::FrameParams,
+		additional_set: Option<std::sync::Arc<vulkano::descriptor_set::PersistentDescriptorSet>>,
+		capture: Option<&PendingCapture>,
+		transfer: Option<(Transfer, std::sync::Arc<vulkano::image::Image>)>,
+	) -> Result<std::sync::Arc<vulkano::command_buffer::PrimaryAutoCommandBuffer>, RendererError> {
+		let params_buffer = self
+			.buffer_allocator
+			.allocate_sized::<shader::FrameParams>()?;
+		*params_buffer.write()? = params;
+
+		let mut extracted_buffers = std::collections::HashMap::new();
+		for (_, name) in self
+			.passes
+			.iter()
+			.flat_map(|compiled| &compiled.pass.extracted_buffers)
+		{
+			if extracted_buffers.contains_key(name) {
+				continue;
+			}
+			let data = self.extract.buffer(name);
+			// Descriptors can't cover zero bytes; shaders see an empty array either way.
+			let buffer = self
+				.buffer_allocator
+				.allocate_slice::<u8>(data.len().max(1) as u64)?;
+			buffer.write()?[..data.len()].copy_from_slice(data);
+			extracted_buffers.insert(name, buffer);
+		}
+
+		let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+			&self.command_buffer_allocator,
+			self.queue.queue_family_index(),
+			vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit,
+		)?;
+		for compiled in &self.passes {
+			let pass = &compiled.pass;
+			let mut writes = Vec::new();
+			for (binding, image_ref) in pass.inputs.iter().chain(&pass.outputs) {
+				let image = match image_ref {
+					graph::ImageRef::Target => image.clone(),
+					graph::ImageRef::Transient(name) => self.transient_images[name].clone(),
+				};
+				writes.push(vulkano::descriptor_set::WriteDescriptorSet::image_view(
+					*binding,
+					vulkano::image::view::ImageView::new_default(image)?,
+				));
+			}
+			for (binding, buffer) in &pass.buffers {
+				writes.push(vulkano::descriptor_set::WriteDescriptorSet::buffer(
+					*binding,
+					buffer.clone(),
+				));
+			}
+			for (binding, name) in &pass.extracted_buffers {
+				writes.push(vulkano::descriptor_set::WriteDescriptorSet::buffer(
+					*binding,
+					extracted_buffers[name].clone(),
+				));
+			}
+
+			let mut sets = Vec::new();
+			if let Some(layout) = compiled.pipeline.layout().set_layouts().get(0) {
+				if layout.bindings().contains_key(&1)
+					&& !writes.iter().any(|write| write.binding() == 1)
+				{
+					writes.push(vulkano::descriptor_set::WriteDescriptorSet::buffer(
+						1,
+						params_buffer.clone(),
+					));
+				}
+				sets.push(vulkano::descriptor_set::PersistentDescriptorSet::new(
+					&self.descriptor_set_allocator,
+					layout.clone(),
+					writes,
+					[],
+				)?);
+			}
+			sets.extend(additional_set.clone());
+
+			builder.bind_pipeline_compute(compiled.pipeline.clone())?;
+			if !sets.is_empty() {
+				builder.bind_descriptor_sets(
+					vulkano::pipeline::PipelineBindPoint::Compute,
+					compiled.pipeline.layout().clone(),
+					0,
+					sets,
+				)?;
+			}
+			builder.dispatch(workgroup_count(
+				params.resolution.into(),
+				compiled.local_size,
+			))?;
+		}
+		if let Some(capture) = capture {
+			builder.copy_image_to_buffer(
+				vulkano::command_buffer::CopyImageToBufferInfo::image_buffer(
+					image.clone(),
+					capture.buffer.clone(),
+				),
+			)?;
+		}
+		match transfer {
+			Some((Transfer::Copy, swapchain_image)) => {
+				builder.copy_image(vulkano::command_buffer::CopyImageInfo::images(
+					image,
+					swapchain_image,
+				))?;
+			},
+			Some((Transfer::Blit, swapchain_image)) => {
+				builder.blit_image(vulkano::command_buffer::BlitImageInfo::images(
+					image,
+					swapchain_image,
+				))?;
+			},
+			None => (),
+		}
+		Ok(builder.build()?)
+	}
+
+	/// Records a command buffer on the present queue blitting `image` onto `swapchain_image`,
+	/// for compute families without graphics support.
+	fn record_present_blit(
+		&self,
+		image: std::sync::Arc<vulkano::image::Image>,
+		swapchain_image: std::sync::Arc<vulkano::image::Image>,
+	) -> Result<std::sync::Arc<vulkano::command_buffer::PrimaryAutoCommandBuffer>, RendererError> {
+		let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+			&self.command_buffer_allocator,
+			self.present_queue.queue_family_index(),
+			vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit,
+		)?;
+		builder.blit_image(vulkano::command_buffer::BlitImageInfo::images(
+			image,
+			swapchain_image,
+		))?;
+		Ok(builder.build()?)
+	}
+
+	/// Requests that the next rendered frame is read back. Retrieve it with
+	/// [`Self::take_captured_frame`] after the next call to `run` or `render_offscreen`.
+	pub fn request_capture(&mut self) {
+		self.capture_requested = true;
+	}
+
+	pub fn take_captured_frame(&mut self) -> Option<screenshot::Frame> {
+		self.captured_frame.take()
+	}
+
+	/// Renders one offscreen frame and reads it back. Fails with [`RendererError::NotHeadless`]
+	/// for a windowed renderer.
+	pub fn capture_offscreen(&mut self) -> Result<screenshot::Frame, RendererError> {
+		self.request_capture();
+		self.render_offscreen(None)?;
+		let frame = self.take_captured_frame();
+		self.capture_requested = false;
+		frame.ok_or(RendererError::NotHeadless)
+	}
+
+	/// Allocates the readback buffer for `image` if a capture was requested. The request is
+	/// cleared even if the image can't be captured.
+	fn prepare_capture(
+		&mut self,
+		image: &std::sync::Arc<vulkano::image::Image>,
+	) -> Result<Option<PendingCapture>, RendererError> {
+		if !std::mem::take(&mut self.capture_requested) {
+			return Ok(None);
+		}
+		if !image
+			.usage()
+			.intersects(vulkano::image::ImageUsage::TRANSFER_SRC)
+		{
+			return Err(RendererError::CaptureNotSupported);
+		}
+		if !screenshot::Frame::supports_format(image.format()) {
+			return Err(RendererError::UnsupportedCaptureFormat(image.format()));
+		}
+
+		let [width, height, _] = image.extent();
+		let buffer = vulkano::buffer::Buffer::new_slice::<u8>(
+			self.memory_allocator.clone(),
+			vulkano::buffer::BufferCreateInfo {
+				usage: vulkano::buffer::BufferUsage::TRANSFER_DST,
+				..Default::default()
+			},
+			vulkano::memory::allocator::AllocationCreateInfo {
+				memory_type_filter: vulkano::memory::allocator::MemoryTypeFilter::PREFER_HOST
+					| vulkano::memory::allocator::MemoryTypeFilter::HOST_RANDOM_ACCESS,
+				..Default::default()
+			},
+			image.format().block_size() * width as u64 * height as u64,
+		)?;
+
+		Ok(Some(PendingCapture {
+			buffer,
+			extent: [width, height],
+			format: image.format(),
+		}))
+	}
+
+	/// Waits for the frame containing `capture` and converts its contents to RGBA8.
+	fn finish_capture<F: GpuFuture>(
+		&mut self,
+		future: &vulkano::sync::future::FenceSignalFuture<F>,
+		capture: PendingCapture,
+	) -> Result<(), RendererError> {
+		future.wait(None)?;
+
+		let texels = capture.buffer.read()?;
+		let frame = screenshot::Frame::from_texels(
+			capture.extent[0],
+			capture.extent[1],
+			capture.format,
+			&texels,
+		)
+		.ok_or(RendererError::UnsupportedCaptureFormat(capture.format))?;
+		self.captured_frame = Some(frame);
+		Ok(())
+	}
+
+	/// Allocator for buffers and images bound by render graph passes.
+	pub fn memory_allocator(
+		&self,
+	) -> &std::sync::Arc<vulkano::memory::allocator::StandardMemoryAllocator> {
+		&self.memory_allocator
+	}
+
+	/// Blocks until all submitted frames have finished executing.
+	pub fn wait_idle(&mut self) -> Result<(), RendererError> {
+		if let Some(previous_frame_end) = self.previous_frame_end.as_mut() {
+			previous_frame_end.cleanup_finished();
+		}
+		unsafe { self.device.wait_idle() }?;
+		Ok(())
+	}
+
+	/// Switches to `present_mode`, or its fallback if unsupported, from the next frame on. Does
+	/// nothing for a headless renderer.
+	pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+		if present_mode != self.present_mode {
+			self.present_mode = present_mode;
+			self.recreate_swapchain = true;
+		}
+	}
+
+	/// The format and color space of the swapchain images, or `None` for a headless renderer.
+	pub fn surface_format(
+		&self,
+	) -> Option<(vulkano::format::Format, vulkano::swapchain::ColorSpace)> {
+		match &self.target {
+			RenderTarget::Swapchain { swapchain, .. } => {
+				Some((swapchain.image_format(), swapchain.image_color_space()))
+			},
+			RenderTarget::Offscreen { .. } => None,
+		}
+	}
+
+	/// The requested present mode, which may have fallen back to another one.
+	pub fn present_mode(&self) -> PresentMode {
+		self.present_mode
+	}
+
+	pub(crate) fn recreate_swapchain(&mut self, value: bool) {
+		self.recreate_swapchain = value;
+	}
+}
+
+/// Number of workgroups of `local_size` needed to cover `extent`.
+pub(crate) fn workgroup_count(extent: [u32; 2], local_size: [u32; 3]) -> [u32; 3] {
+	[
+		extent[0].div_ceil(local_size[0]),
+		extent[1].div_ceil(local_size[1]),
+		1,
+	]
+}
+
+/// How images used on both `queue` and `present_queue` are shared: concurrently if the queues
+/// belong to different families, which avoids queue family ownership transfers between them.
+fn queue_sharing<I: FromIterator<u32> + IntoIterator<Item = u32>>(
+	queue: &vulkano::device::Queue,
+	present_queue: &vulkano::device::Queue,
+) -> vulkano::sync::Sharing<I> {
+	let families = [
+		queue.queue_family_index(),
+		present_queue.queue_family_index(),
+	];
+	if families[0] == families[1] {
+		vulkano::sync::Sharing::Exclusive
+	} else {
+		vulkano::sync::Sharing::Concurrent(families.into_iter().collect())
+	}
+}
+
+/// Whether `queue` can record blits, which needs a graphics-capable family.
+fn supports_blit(queue: &vulkano::device::Queue) -> bool {
+	let families = queue.device().physical_device().queue_family_properties();
+	families[queue.queue_family_index() as usize]
+		.queue_flags
+		.intersects(vulkano::device::QueueFlags::GRAPHICS)
+}
+
+/// The UNORM format with the same layout as the sRGB format `format`.
+fn unorm_counterpart(format: vulkano::format::Format) -> Option<vulkano::format::Format> {
+	match format {
+		vulkano::format::Format::B8G8R8A8_SRGB => Some(vulkano::format::Format::B8G8R8A8_UNORM),
+		vulkano::format::Format::R8G8B8A8_SRGB => Some(vulkano::format::Format::R8G8B8A8_UNORM),
+		vulkano::format::Format::A8B8G8R8_SRGB_PACK32 => {
+			Some(vulkano::format::Format::A8B8G8R8_UNORM_PACK32)
+		},
+		_ => None,
+	}
+}
@@ -0,0 +1,475 @@
+//! Recording the input of a run and replaying it deterministically, to turn a bug report into a
+//! regression test.
+//!
+//! A recording is the sequence of [`InputEvent`]s the engine's [`crate::input::Input`] saw, the
+//! time of every frame and the fixed ticks each frame ran. Apps only get input through these,
+//! from the input state or [`crate::App::input_event`], so recorded and live runs take the same
+//! path. Replaying feeds the events back and advances the [`crate::GameLoop`] by the recorded
+//! frame times instead of the clock, so every update sees the same input on the same tick. The
+//! shader's time is the simulated time in both runs, so replayed frames match the recorded ones.
+//! Each frame's ticks are checked against the recorded ones, which catches a changed
+//! [`crate::LoopConfig`].
+//!
+//! Record with `--record PATH` and replay without a window with `--replay PATH`, optionally with
+//! `--screenshot PATH` to save the final frame.
+//!
+//! The file starts with [`MAGIC`] and a version byte, followed by records of a tag byte and a
+//! little-endian payload. Keys are stored by their [`Binding::name`], which every key winit
+//! reports has, and mouse buttons by number.
+
+use crate::{
+	gamepad::{GamepadAxis, GamepadButton, GamepadEvent, GamepadId},
+	input::{Binding, InputEvent, MouseButton},
+};
+use shader::glam::vec2;
+
+pub const MAGIC: &[u8; 8] = b"DOTINPUT";
+const VERSION: u8 = 2;
+
+const FRAME: u8 = 0;
+const TICK: u8 = 1;
+const KEY: u8 = 2;
+const MOUSE_BUTTON: u8 = 3;
+const CURSOR_MOVED: u8 = 4;
+const CURSOR_LEFT: u8 = 5;
+const SCROLL: u8 = 6;
+const RESIZED: u8 = 7;
+const FOCUS_LOST: u8 = 8;
+const GAMEPAD_CONNECTED: u8 = 9;
+const GAMEPAD_DISCONNECTED: u8 = 10;
+const GAMEPAD_BUTTON: u8 = 11;
+const GAMEPAD_AXIS: u8 = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+	Event(InputEvent),
+	/// A rendered frame, this long after the previous one.
+	Frame(std::time::Duration),
+	/// A fixed tick run by the preceding frame.
+	Tick(u64),
+}
+
+/// Appends records to a file as they happen.
+pub struct Recorder {
+	writer: std::io::BufWriter<std::fs::File>,
+}
+
+impl Recorder {
+	/// Creates or truncates the file at `path` and writes the header.
+	pub fn create(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+		let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+		std::io::Write::write_all(&mut writer, MAGIC)?;
+		std::io::Write::write_all(&mut writer, &[VERSION])?;
+		Ok(Self { writer })
+	}
+
+	/// Fails without writing anything for a key without a [`Binding::name`].
+	pub fn record(&mut self, record: &Record) -> Result<(), ReplayError> {
+		let mut bytes = Vec::new();
+		encode(record, &mut bytes)?;
+		std::io::Write::write_all(&mut self.writer, &bytes)?;
+		Ok(())
+	}
+
+	/// Writes the buffered records out, so a crash loses at most the current frame.
+	pub fn flush(&mut self) -> std::io::Result<()> {
+		std::io::Write::flush(&mut self.writer)
+	}
+}
+
+/// A recording read back.
+pub struct Recording {
+	pub records: Vec<Record>,
+}
+
+impl Recording {
+	pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, ReplayError> {
+		Self::parse(&std::fs::read(path)?)
+	}
+
+	pub fn parse(bytes: &[u8]) -> Result<Self, ReplayError> {
+		let mut reader = Reader { bytes };
+		if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+			return Err(ReplayError::NotARecording);
+		}
+		let version = reader.u8()?;
+		if version != VERSION {
+			return Err(ReplayError::UnsupportedVersion(version));
+		}
+		let mut records = Vec::new();
+		while !reader.bytes.is_empty() {
+			records.push(decode(&mut reader)?);
+		}
+		Ok(Self { records })
+	}
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+	Io(std::io::Error),
+	/// The file doesn't start with [`MAGIC`].
+	NotARecording,
+	/// The file was written by a different version of the engine.
+	UnsupportedVersion(u8),
+	/// The file ends in the middle of a record.
+	Truncated,
+	UnknownRecord(u8),
+	/// A key, mouse button or gamepad input that this version of the engine doesn't know.
+	UnknownInput(String),
+	/// A frame ran a different tick than the recorded one, or none where one was recorded.
+	Diverged {
+		frame: usize,
+		tick: u64,
+	},
+}
+
+impl std::fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Io(_) => write!(f, "failed to access the recording"),
+			Self::NotARecording => write!(f, "not an input recording"),
+			Self::UnsupportedVersion(version) => {
+				write!(f, "unsupported input recording version {version}")
+			},
+			Self::Truncated => write!(f, "the recording is truncated"),
+			Self::UnknownRecord(tag) => write!(f, "unknown record type {tag}"),
+			Self::UnknownInput(name) => write!(f, "unknown input {name:?}"),
+			Self::Diverged { frame, tick } => write!(
+				f,
+				"replay diverged from the recording at tick {tick} of frame {frame}"
+			),
+		}
+	}
+}
+
+impl std::error::Error for ReplayError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<std::io::Error> for ReplayError {
+	fn from(e: std::io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+fn encode(record: &Record, bytes: &mut Vec<u8>) -> Result<(), ReplayError> {
+	match record {
+		Record::Frame(frame_time) => {
+			bytes.push(FRAME);
+			bytes.extend_from_slice(&(frame_time.as_nanos() as u64).to_le_bytes());
+		},
+		Record::Tick(index) => {
+			bytes.push(TICK);
+			bytes.extend_from_slice(&index.to_le_bytes());
+		},
+		Record::Event(event) => match event {
+			InputEvent::Key { key, pressed } => {
+				let name = Binding::Key(*key)
+					.name()
+					.ok_or_else(|| ReplayError::UnknownInput(format!("{key:?}")))?;
+				bytes.push(KEY);
+				bytes.push(name.len() as u8);
+				bytes.extend_from_slice(name.as_bytes());
+				bytes.push(*pressed as u8);
+			},
+			InputEvent::MouseButton { button, pressed } => {
+				let (kind, number) = match *button {
+					MouseButton::Left => (0, 0),
+					MouseButton::Right => (1, 0),
+					MouseButton::Middle => (2, 0),
+					MouseButton::Back => (3, 0),
+					MouseButton::Forward => (4, 0),
+					MouseButton::Other(number) => (5, number),
+				};
+				bytes.push(MOUSE_BUTTON);
+				bytes.push(kind);
+				bytes.extend_from_slice(&number.to_le_bytes());
+				bytes.push(*pressed as u8);
+			},
+			InputEvent::CursorMoved(position) => {
+				bytes.push(CURSOR_MOVED);
+				bytes.extend_from_slice(&position.x.to_le_bytes());
+				bytes.extend_from_slice(&position.y.to_le_bytes());
+			},
+			InputEvent::CursorLeft => bytes.push(CURSOR_LEFT),
+			InputEvent::Scroll(lines) => {
+				bytes.push(SCROLL);
+				bytes.extend_from_slice(&lines.x.to_le_bytes());
+				bytes.extend_from_slice(&lines.y.to_le_bytes());
+			},
+			InputEvent::Resized(size) => {
+				bytes.push(RESIZED);
+				bytes.extend_from_slice(&size[0].to_le_bytes());
+				bytes.extend_from_slice(&size[1].to_le_bytes());
+			},
+			InputEvent::FocusLost => bytes.push(FOCUS_LOST),
+			InputEvent::Gamepad(event) => match event {
+				GamepadEvent::Connected { gamepad, name } => {
+					bytes.push(GAMEPAD_CONNECTED);
+					bytes.extend_from_slice(&gamepad.0.to_le_bytes());
+					let name = name.as_bytes();
+					bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+					bytes.extend_from_slice(name);
+				},
+				GamepadEvent::Disconnected { gamepad } => {
+					bytes.push(GAMEPAD_DISCONNECTED);
+					bytes.extend_from_slice(&gamepad.0.to_le_bytes());
+				},
+				GamepadEvent::Button {
+					gamepad,
+					button,
+					pressed,
+				} => {
+					bytes.push(GAMEPAD_BUTTON);
+					bytes.extend_from_slice(&gamepad.0.to_le_bytes());
+					bytes.push(GamepadButton::ALL.iter().position(|b| b == button).unwrap() as u8);
+					bytes.push(*pressed as u8);
+				},
+				GamepadEvent::Axis {
+					gamepad,
+					axis,
+					value,
+				} => {
+					bytes.push(GAMEPAD_AXIS);
+					bytes.extend_from_slice(&gamepad.0.to_le_bytes());
+					bytes.push(GamepadAxis::ALL.iter().position(|a| a == axis).unwrap() as u8);
+					bytes.extend_from_slice(&value.to_le_bytes());
+				},
+			},
+		},
+	}
+	Ok(())
+}
+
+fn decode(reader: &mut Reader<'_>) -> Result<Record, ReplayError> {
+	let event = match reader.u8()? {
+		FRAME => {
+			return Ok(Record::Frame(std::time::Duration::from_nanos(
+				reader.u64()?,
+			)));
+		},
+		TICK => return Ok(Record::Tick(reader.u64()?)),
+		KEY => {
+			let length = reader.u8()? as usize;
+			let name = String::from_utf8_lossy(reader.take(length)?).into_owned();
+			match Binding::from_name(&name) {
+				Some(Binding::Key(key)) => InputEvent::Key {
+					key,
+					pressed: reader.u8()? != 0,
+				},
+				_ => return Err(ReplayError::UnknownInput(name)),
+			}
+		},
+		MOUSE_BUTTON => {
+			let kind = reader.u8()?;
+			let number = reader.u16()?;
+			let button = match kind {
+				0 => MouseButton::Left,
+				1 => MouseButton::Right,
+				2 => MouseButton::Middle,
+				3 => MouseButton::Back,
+				4 => MouseButton::Forward,
+				5 => MouseButton::Other(number),
+				_ => return Err(ReplayError::UnknownInput(format!("mouse button {kind}"))),
+			};
+			InputEvent::MouseButton {
+				button,
+				pressed: reader.u8()? != 0,
+			}
+		},
+		CURSOR_MOVED => InputEvent::CursorMoved(vec2(reader.f32()?, reader.f32()?)),
+		CURSOR_LEFT => InputEvent::CursorLeft,
+		SCROLL => InputEvent::Scroll(vec2(reader.f32()?, reader.f32()?)),
+		RESIZED => InputEvent::Resized([reader.u32()?, reader.u32()?]),
+		FOCUS_LOST => InputEvent::FocusLost,
+		GAMEPAD_CONNECTED => {
+			let gamepad = GamepadId(reader.u32()?);
+			let length = reader.u32()? as usize;
+			let name = String::from_utf8_lossy(reader.take(length)?).into_owned();
+			InputEvent::Gamepad(GamepadEvent::Connected { gamepad, name })
+		},
+		GAMEPAD_DISCONNECTED => InputEvent::Gamepad(GamepadEvent::Disconnected {
+			gamepad: GamepadId(reader.u32()?),
+		}),
+		GAMEPAD_BUTTON => {
+			let gamepad = GamepadId(reader.u32()?);
+			let index = reader.u8()?;
+			let button = *GamepadButton::ALL
+				.get(index as usize)
+				.ok_or_else(|| ReplayError::UnknownInput(format!("gamepad button {index}")))?;
+			InputEvent::Gamepad(GamepadEvent::Button {
+				gamepad,
+				button,
+				pressed: reader.u8()? != 0,
+			})
+		},
+		GAMEPAD_AXIS => {
+			let gamepad = GamepadId(reader.u32()?);
+			let index = reader.u8()?;
+			let axis = *GamepadAxis::ALL
+				.get(index as usize)
+				.ok_or_else(|| ReplayError::UnknownInput(format!("gamepad axis {index}")))?;
+			InputEvent::Gamepad(GamepadEvent::Axis {
+				gamepad,
+				axis,
+				value: reader.f32()?,
+			})
+		},
+		tag => return Err(ReplayError::UnknownRecord(tag)),
+	};
+	Ok(Record::Event(event))
+}
+
+/// Reads little-endian values from the front of `bytes`.
+struct Reader<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, count: usize) -> Result<&'a [u8], ReplayError> {
+		if self.bytes.len() < count {
+			return Err(ReplayError::Truncated);
+		}
+		let (taken, rest) = self.bytes.split_at(count);
+		self.bytes = rest;
+		Ok(taken)
+	}
+
+	fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+		Ok(self.take(N)?.try_into().unwrap())
+	}
+
+	fn u8(&mut self) -> Result<u8, ReplayError> {
+		Ok(self.array::<1>()?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, ReplayError> {
+		self.array().map(u16::from_le_bytes)
+	}
+
+	fn u32(&mut self) -> Result<u32, ReplayError> {
+		self.array().map(u32::from_le_bytes)
+	}
+
+	fn u64(&mut self) -> Result<u64, ReplayError> {
+		self.array().map(u64::from_le_bytes)
+	}
+
+	fn f32(&mut self) -> Result<f32, ReplayError> {
+		self.array().map(f32::from_le_bytes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::input::KeyCode;
+	use std::time::Duration;
+
+	fn records() -> Vec<Record> {
+		let gamepad = GamepadId(3);
+		vec![
+			Record::Event(InputEvent::Resized([1280, 720])),
+			Record::Event(InputEvent::Key {
+				key: KeyCode::Space,
+				pressed: true,
+			}),
+			Record::Event(InputEvent::Key {
+				key: KeyCode::MediaPlayPause,
+				pressed: false,
+			}),
+			Record::Event(InputEvent::MouseButton {
+				button: MouseButton::Left,
+				pressed: true,
+			}),
+			Record::Event(InputEvent::MouseButton {
+				button: MouseButton::Other(9),
+				pressed: false,
+			}),
+			Record::Event(InputEvent::CursorMoved(vec2(12.5, -3.0))),
+			Record::Event(InputEvent::CursorLeft),
+			Record::Event(InputEvent::Scroll(vec2(0.0, 1.5))),
+			Record::Event(InputEvent::FocusLost),
+			Record::Event(InputEvent::Gamepad(GamepadEvent::Connected {
+				gamepad,
+				name: "Pad ✓".to_owned(),
+			})),
+			Record::Event(InputEvent::Gamepad(GamepadEvent::Button {
+				gamepad,
+				button: GamepadButton::DPadRight,
+				pressed: true,
+			})),
+			Record::Event(InputEvent::Gamepad(GamepadEvent::Axis {
+				gamepad,
+				axis: GamepadAxis::RightTrigger,
+				value: 0.25,
+			})),
+			Record::Event(InputEvent::Gamepad(GamepadEvent::Disconnected { gamepad })),
+			Record::Frame(Duration::from_nanos(16_666_667)),
+			Record::Tick(0),
+			Record::Tick(u64::MAX),
+		]
+	}
+
+	fn file(records: &[Record]) -> Vec<u8> {
+		let mut bytes = MAGIC.to_vec();
+		bytes.push(VERSION);
+		for record in records {
+			encode(record, &mut bytes).unwrap();
+		}
+		bytes
+	}
+
+	#[test]
+	fn round_trips_every_record() {
+		let records = records();
+		assert_eq!(Recording::parse(&file(&records)).unwrap().records, records);
+	}
+
+	#[test]
+	fn round_trips_through_a_file() {
+		let path = std::env::temp_dir().join(format!("dot-replay-{}.input", std::process::id()));
+		let mut recorder = Recorder::create(&path).unwrap();
+		for record in records() {
+			recorder.record(&record).unwrap();
+		}
+		recorder.flush().unwrap();
+		let recording = Recording::load(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(recording.unwrap().records, records());
+	}
+
+	#[test]
+	fn rejects_malformed_recordings() {
+		assert!(matches!(
+			Recording::parse(b"DOTINPU"),
+			Err(ReplayError::NotARecording)
+		));
+		assert!(matches!(
+			Recording::parse(b"DOTINPUT\x01"),
+			Err(ReplayError::UnsupportedVersion(1))
+		));
+		let bytes = file(&records());
+		assert!(matches!(
+			Recording::parse(&bytes[..bytes.len() - 1]),
+			Err(ReplayError::Truncated)
+		));
+		let mut bytes = file(&[]);
+		bytes.push(200);
+		assert!(matches!(
+			Recording::parse(&bytes),
+			Err(ReplayError::UnknownRecord(200))
+		));
+		let mut bytes = file(&[]);
+		bytes.extend_from_slice(&[KEY, 4, b'K', b'e', b'y', b'!', 1]);
+		assert!(matches!(
+			Recording::parse(&bytes),
+			Err(ReplayError::UnknownInput(name)) if name == "Key!"
+		));
+	}
+}
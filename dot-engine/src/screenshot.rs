@@ -0,0 +1,246 @@
+/// An RGBA8 frame read back from the GPU, rows top to bottom.
+pub struct Frame {
+	pub width: u32,
+	pub height: u32,
+	pub pixels: Vec<u8>,
+}
+
+impl Frame {
+	/// Whether [`Self::from_texels`] can convert texels in `format`.
+	pub fn supports_format(format: vulkano::format::Format) -> bool {
+		matches!(
+			format,
+			vulkano::format::Format::R8G8B8A8_UNORM
+				| vulkano::format::Format::R8G8B8A8_SRGB
+				| vulkano::format::Format::B8G8R8A8_UNORM
+				| vulkano::format::Format::B8G8R8A8_SRGB
+				| vulkano::format::Format::A2B10G10R10_UNORM_PACK32
+				| vulkano::format::Format::A2R10G10B10_UNORM_PACK32
+				| vulkano::format::Format::R16G16B16A16_SFLOAT
+		)
+	}
+
+	/// Builds a frame from raw texel bytes in `format`, converting to RGBA8.
+	/// Returns `None` for formats that cannot be converted.
+	pub fn from_texels(
+		width: u32,
+		height: u32,
+		format: vulkano::format::Format,
+		texels: &[u8],
+	) -> Option<Self> {
+		if !Self::supports_format(format) {
+			return None;
+		}
+		let mut pixels = texels.to_vec();
+		match format {
+			vulkano::format::Format::R8G8B8A8_UNORM | vulkano::format::Format::R8G8B8A8_SRGB => (),
+			vulkano::format::Format::B8G8R8A8_UNORM | vulkano::format::Format::B8G8R8A8_SRGB => {
+				for pixel in pixels.chunks_exact_mut(4) {
+					pixel.swap(0, 2);
+				}
+			},
+			// 10-bit channels keep their top 8 bits, and 2-bit alpha is scaled up.
+			vulkano::format::Format::A2B10G10R10_UNORM_PACK32
+			| vulkano::format::Format::A2R10G10B10_UNORM_PACK32 => {
+				for pixel in pixels.chunks_exact_mut(4) {
+					let texel = u32::from_le_bytes(pixel.try_into().unwrap());
+					let channel = |shift: u32| (texel >> (shift + 2)) as u8;
+					let (red, blue) = match format {
+						vulkano::format::Format::A2B10G10R10_UNORM_PACK32 => (0, 20),
+						_ => (20, 0),
+					};
+					pixel.copy_from_slice(&[
+						channel(red),
+						channel(10),
+						channel(blue),
+						(texel >> 30) as u8 * 85,
+					]);
+				}
+			},
+			// Values are clamped to 0..=1, so HDR highlights clip.
+			vulkano::format::Format::R16G16B16A16_SFLOAT => {
+				pixels = texels
+					.chunks_exact(2)
+					.map(|half| {
+						let value = f16_to_f32(u16::from_le_bytes([half[0], half[1]]));
+						(value.clamp(0.0, 1.0) * 255.0).round() as u8
+					})
+					.collect();
+			},
+			_ => unreachable!("checked by supports_format"),
+		}
+		Some(Self {
+			width,
+			height,
+			pixels,
+		})
+	}
+
+	/// Reads a PNG from `path`, expanding grayscale and RGB images to RGBA8.
+	pub fn load_png(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+		let file = std::io::BufReader::new(std::fs::File::open(path)?);
+		let mut decoder = png::Decoder::new(file);
+		decoder.set_transformations(png::Transformations::normalize_to_color8());
+		let mut reader = decoder.read_info()?;
+		let mut buffer = vec![0; reader.output_buffer_size()];
+		let info = reader.next_frame(&mut buffer)?;
+		buffer.truncate(info.buffer_size());
+
+		let pixels = match info.color_type {
+			png::ColorType::Rgba => buffer,
+			png::ColorType::Rgb => buffer
+				.chunks_exact(3)
+				.flat_map(|p| [p[0], p[1], p[2], 255])
+				.collect(),
+			png::ColorType::GrayscaleAlpha => buffer
+				.chunks_exact(2)
+				.flat_map(|p| [p[0], p[0], p[0], p[1]])
+				.collect(),
+			png::ColorType::Grayscale => buffer.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+			png::ColorType::Indexed => unreachable!("indexed images are expanded by the decoder"),
+		};
+		Ok(Self {
+			width: info.width,
+			height: info.height,
+			pixels,
+		})
+	}
+
+	/// Writes the frame to `path`, as PPM if the extension is `ppm` and PNG otherwise.
+	pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+		let path = path.as_ref();
+		let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+		if path
+			.extension()
+			.is_some_and(|extension| extension.eq_ignore_ascii_case("ppm"))
+		{
+			self.write_ppm(file)
+		} else {
+			self.write_png(file)
+		}
+	}
+
+	pub fn write_png(&self, writer: impl std::io::Write) -> std::io::Result<()> {
+		let mut encoder = png::Encoder::new(writer, self.width, self.height);
+		encoder.set_color(png::ColorType::Rgba);
+		encoder.set_depth(png::BitDepth::Eight);
+		let mut writer = encoder.write_header()?;
+		writer.write_image_data(&self.pixels)?;
+		Ok(())
+	}
+
+	/// Writes a binary (P6) PPM; alpha is dropped.
+	pub fn write_ppm(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+		write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+		for pixel in self.pixels.chunks_exact(4) {
+			writer.write_all(&pixel[..3])?;
+		}
+		writer.flush()
+	}
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+	let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+	let exponent = i32::from((half >> 10) & 0x1f);
+	let mantissa = f32::from(half & 0x3ff);
+	sign * match exponent {
+		0 => mantissa * 2f32.powi(-24),
+		0x1f if mantissa == 0.0 => f32::INFINITY,
+		0x1f => f32::NAN,
+		_ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use vulkano::format::Format;
+
+	fn frame() -> Frame {
+		Frame {
+			width: 2,
+			height: 1,
+			pixels: vec![255, 128, 0, 255, 10, 20, 30, 40],
+		}
+	}
+
+	fn temp_path(extension: &str) -> std::path::PathBuf {
+		std::env::temp_dir().join(format!("dot-screenshot-{}.{extension}", std::process::id()))
+	}
+
+	#[test]
+	fn swaps_bgra_to_rgba() {
+		let frame = Frame::from_texels(1, 1, Format::B8G8R8A8_SRGB, &[1, 2, 3, 4]).unwrap();
+		assert_eq!(frame.pixels, [3, 2, 1, 4]);
+		let frame = Frame::from_texels(1, 1, Format::R8G8B8A8_UNORM, &[1, 2, 3, 4]).unwrap();
+		assert_eq!(frame.pixels, [1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn unpacks_ten_bit_channels() {
+		// Full scale in bits 0..10, half in bits 10..20, zero in bits 20..30, alpha 3.
+		let texel: u32 = 0x3ff | 0x200 << 10 | 3 << 30;
+		let texels = texel.to_le_bytes();
+		let frame = Frame::from_texels(1, 1, Format::A2B10G10R10_UNORM_PACK32, &texels).unwrap();
+		assert_eq!(frame.pixels, [255, 128, 0, 255]);
+		let frame = Frame::from_texels(1, 1, Format::A2R10G10B10_UNORM_PACK32, &texels).unwrap();
+		assert_eq!(frame.pixels, [0, 128, 255, 255]);
+
+		let texel = 1_u32 << 30;
+		let frame =
+			Frame::from_texels(1, 1, Format::A2B10G10R10_UNORM_PACK32, &texel.to_le_bytes())
+				.unwrap();
+		assert_eq!(frame.pixels, [0, 0, 0, 85]);
+	}
+
+	#[test]
+	fn clamps_half_floats() {
+		let texels: Vec<u8> = [0x3c00_u16, 0x3800, 0xbc00, 0x4000]
+			.into_iter()
+			.flat_map(u16::to_le_bytes)
+			.collect();
+		let frame = Frame::from_texels(1, 1, Format::R16G16B16A16_SFLOAT, &texels).unwrap();
+		assert_eq!(frame.pixels, [255, 128, 0, 255]);
+	}
+
+	#[test]
+	fn rejects_other_formats() {
+		assert!(!Frame::supports_format(Format::R32_SFLOAT));
+		assert!(Frame::from_texels(1, 1, Format::R32_SFLOAT, &[0; 4]).is_none());
+	}
+
+	#[test]
+	fn converts_half_floats() {
+		assert_eq!(f16_to_f32(0x0000), 0.0);
+		assert_eq!(f16_to_f32(0x3c00), 1.0);
+		assert_eq!(f16_to_f32(0xc000), -2.0);
+		assert_eq!(f16_to_f32(0x7bff), 65504.0);
+		// Subnormals.
+		assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+		assert_eq!(f16_to_f32(0x03ff), 1023.0 * 2f32.powi(-24));
+		assert_eq!(f16_to_f32(0x8001), -(2f32.powi(-24)));
+		assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+		assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+		assert!(f16_to_f32(0x7e00).is_nan());
+	}
+
+	#[test]
+	fn round_trips_through_png() {
+		let path = temp_path("png");
+		frame().save(&path).unwrap();
+		let loaded = Frame::load_png(&path);
+		std::fs::remove_file(&path).unwrap();
+		let loaded = loaded.unwrap();
+		assert_eq!([loaded.width, loaded.height], [2, 1]);
+		assert_eq!(loaded.pixels, frame().pixels);
+	}
+
+	#[test]
+	fn saves_ppm_without_alpha() {
+		let path = temp_path("ppm");
+		frame().save(&path).unwrap();
+		let bytes = std::fs::read(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(bytes.unwrap(), b"P6\n2 1\n255\n\xff\x80\x00\x0a\x14\x1e");
+	}
+}
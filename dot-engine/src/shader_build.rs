@@ -0,0 +1,16 @@
+//! How the shader crate is compiled, shared by `build.rs` and the `hot-reload` feature so that
+//! rebuilt modules match the embedded ones.
+//!
+//! Like `reflect`, this module doesn't depend on the rest of the crate, as `build.rs` includes it.
+
+/// The target the shader crate is compiled for.
+pub const TARGET: &str = "spirv-unknown-vulkan1.0";
+
+/// A builder for the shader crate at `path`, producing one module per entry point.
+pub fn builder(path: impl AsRef<std::path::Path>) -> spirv_builder::SpirvBuilder {
+	spirv_builder::SpirvBuilder::new(path, TARGET)
+		.capability(spirv_builder::Capability::StorageImageWriteWithoutFormat)
+		.extension("SPV_KHR_storage_buffer_storage_class")
+		.release(true)
+		.multimodule(true)
+}
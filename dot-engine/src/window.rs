@@ -0,0 +1,299 @@
+//! The window [`crate::run`] opens: its size, fullscreen mode, monitor and cursor, all of which
+//! can be changed while running through [`Window::set_config`].
+
+/// How the window covers the screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowMode {
+	#[default]
+	Windowed,
+	/// A borderless window covering the monitor, at the monitor's current video mode.
+	BorderlessFullscreen,
+	/// The monitor switched to the video mode closest to [`WindowConfig::size`], with the
+	/// highest refresh rate. Falls back to borderless on platforms without video modes.
+	ExclusiveFullscreen,
+}
+
+/// Which monitor fullscreen modes use.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum MonitorSelection {
+	/// The monitor the window is on, or the primary one before it is shown.
+	#[default]
+	Current,
+	Primary,
+	/// An index into the available monitors, in the order the platform lists them. Falls back
+	/// to the primary monitor if out of range.
+	Index(usize),
+}
+
+/// How the cursor is confined while over a focused window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CursorGrab {
+	#[default]
+	None,
+	/// Kept inside the window.
+	Confined,
+	/// Kept in place, for mouse look. Falls back to [`Self::Confined`] where unsupported, e.g.
+	/// on X11.
+	Locked,
+}
+
+/// How presented frames are synchronized with the display. Modes the surface doesn't support
+/// fall back to the next one in their order: immediate, mailbox, FIFO, which is always available.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PresentMode {
+	/// Vsync: frames wait for the display's refresh, and rendering blocks when they queue up.
+	#[default]
+	Fifo,
+	/// Vsync without blocking: the newest frame replaces any frame still waiting.
+	Mailbox,
+	/// No vsync: frames are shown as soon as they are ready, which may tear.
+	Immediate,
+}
+
+impl PresentMode {
+	/// `self` followed by its fallbacks, in order of preference.
+	pub(crate) fn fallbacks(self) -> &'static [Self] {
+		match self {
+			Self::Fifo => &[Self::Fifo],
+			Self::Mailbox => &[Self::Mailbox, Self::Fifo],
+			Self::Immediate => &[Self::Immediate, Self::Mailbox, Self::Fifo],
+		}
+	}
+}
+
+/// The color space presented frames are in. Shaders write values encoded for it: sRGB-encoded
+/// for [`Self::Srgb`], PQ-encoded Rec. 2020 for [`Self::Hdr10`] and linear, possibly beyond
+/// `0..=1`, for [`Self::ScRgb`]. HDR color spaces fall back to sRGB where the display or driver
+/// lacks them; [`crate::Renderer::surface_format`] tells which one was chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+	#[default]
+	Srgb,
+	/// HDR10: Rec. 2020 primaries with the ST 2084 transfer function, usually in a 10-bit format.
+	Hdr10,
+	/// Extended linear sRGB in a half-float format.
+	ScRgb,
+}
+
+impl ColorSpace {
+	pub(crate) fn to_vulkan(self) -> vulkano::swapchain::ColorSpace {
+		match self {
+			Self::Srgb => vulkano::swapchain::ColorSpace::SrgbNonLinear,
+			Self::Hdr10 => vulkano::swapchain::ColorSpace::Hdr10St2084,
+			Self::ScRgb => vulkano::swapchain::ColorSpace::ExtendedSrgbLinear,
+		}
+	}
+}
+
+/// How the renderer's swapchain presents to the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurfaceConfig {
+	pub present_mode: PresentMode,
+	/// Format of the swapchain images, tried before the formats suited to `color_space`. `None`
+	/// picks the best one the surface supports.
+	pub format: Option<vulkano::format::Format>,
+	pub color_space: ColorSpace,
+	/// How the window's alpha blends with what is behind it, which the surface must support.
+	pub composite_alpha: vulkano::swapchain::CompositeAlpha,
+}
+
+impl Default for SurfaceConfig {
+	fn default() -> Self {
+		Self {
+			present_mode: PresentMode::Fifo,
+			format: None,
+			color_space: ColorSpace::Srgb,
+			composite_alpha: vulkano::swapchain::CompositeAlpha::Opaque,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowConfig {
+	pub title: String,
+	/// Initial inner size in physical pixels, also the size of headless renders.
+	pub size: [u32; 2],
+	/// Smallest inner size the window can be resized to, in physical pixels.
+	pub min_size: Option<[u32; 2]>,
+	pub resizable: bool,
+	pub mode: WindowMode,
+	pub monitor: MonitorSelection,
+	pub cursor_grab: CursorGrab,
+	pub cursor_visible: bool,
+}
+
+impl Default for WindowConfig {
+	fn default() -> Self {
+		Self {
+			title: "Dot".to_owned(),
+			size: [800, 600],
+			min_size: None,
+			resizable: true,
+			mode: WindowMode::Windowed,
+			monitor: MonitorSelection::Current,
+			cursor_grab: CursorGrab::None,
+			cursor_visible: true,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum WindowError {
+	/// Creating the window failed.
+	Os(winit::error::OsError),
+	/// Neither grabbing mode is supported. [`Window::set_config`] returns this, while the
+	/// initial config falls back to a free cursor.
+	CursorGrab(winit::error::ExternalError),
+}
+
+impl std::fmt::Display for WindowError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Os(_) => write!(f, "failed to create the window"),
+			Self::CursorGrab(_) => write!(f, "failed to grab the cursor"),
+		}
+	}
+}
+
+impl std::error::Error for WindowError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Os(e) => Some(e),
+			Self::CursorGrab(e) => Some(e),
+		}
+	}
+}
+
+impl From<winit::error::OsError> for WindowError {
+	fn from(e: winit::error::OsError) -> Self {
+		Self::Os(e)
+	}
+}
+
+/// The app's window and the configuration it was last given.
+pub struct Window {
+	window: std::sync::Arc<winit::window::Window>,
+	config: WindowConfig,
+}
+
+impl Window {
+	pub(crate) fn new<T>(
+		event_loop: &winit::event_loop::EventLoop<T>,
+		config: WindowConfig,
+	) -> Result<Self, WindowError> {
+		let window = winit::window::WindowBuilder::new()
+			.with_title(&config.title)
+			.with_inner_size(physical_size(config.size))
+			.with_resizable(config.resizable)
+			.build(event_loop)?;
+		if let Some(min_size) = config.min_size {
+			window.set_min_inner_size(Some(physical_size(min_size)));
+		}
+		let mut window = Self {
+			window: std::sync::Arc::new(window),
+			config,
+		};
+		window.apply_mode(&window.config);
+		if let Err(e) = window.apply_cursor(&window.config) {
+			println!(
+				"failed to grab the cursor, leaving it free: {}",
+				crate::report(&e)
+			);
+			window.config.cursor_grab = CursorGrab::None;
+			window
+				.window
+				.set_cursor_visible(window.config.cursor_visible);
+		}
+		Ok(window)
+	}
+
+	pub fn config(&self) -> &WindowConfig {
+		&self.config
+	}
+
+	/// Applies the settings of `config` that differ from the current ones. The cursor is applied
+	/// first, so if grabbing it fails nothing changes.
+	pub fn set_config(&mut self, config: WindowConfig) -> Result<(), WindowError> {
+		if (config.cursor_grab, config.cursor_visible)
+			!= (self.config.cursor_grab, self.config.cursor_visible)
+		{
+			self.apply_cursor(&config)?;
+		}
+		let window = &self.window;
+		if config.title != self.config.title {
+			window.set_title(&config.title);
+		}
+		if config.size != self.config.size {
+			let _ = window.request_inner_size(physical_size(config.size));
+		}
+		if config.min_size != self.config.min_size {
+			window.set_min_inner_size(config.min_size.map(physical_size));
+		}
+		if config.resizable != self.config.resizable {
+			window.set_resizable(config.resizable);
+		}
+		if (&config.mode, &config.monitor) != (&self.config.mode, &self.config.monitor) {
+			self.apply_mode(&config);
+		}
+		self.config = config;
+		Ok(())
+	}
+
+	/// The winit window, e.g. to query its size or scale factor.
+	pub fn winit_window(&self) -> &std::sync::Arc<winit::window::Window> {
+		&self.window
+	}
+
+	fn apply_mode(&self, config: &WindowConfig) {
+		let fullscreen = match config.mode {
+			WindowMode::Windowed => None,
+			WindowMode::BorderlessFullscreen => Some(winit::window::Fullscreen::Borderless(
+				self.monitor(&config.monitor),
+			)),
+			WindowMode::ExclusiveFullscreen => {
+				let monitor = self.monitor(&config.monitor);
+				let video_mode = monitor.as_ref().and_then(|monitor| {
+					monitor.video_modes().min_by_key(|mode| {
+						let size = mode.size();
+						let distance = size.width.abs_diff(config.size[0])
+							+ size.height.abs_diff(config.size[1]);
+						(distance, std::cmp::Reverse(mode.refresh_rate_millihertz()))
+					})
+				});
+				Some(match video_mode {
+					Some(video_mode) => winit::window::Fullscreen::Exclusive(video_mode),
+					None => winit::window::Fullscreen::Borderless(monitor),
+				})
+			},
+		};
+		self.window.set_fullscreen(fullscreen);
+	}
+
+	fn apply_cursor(&self, config: &WindowConfig) -> Result<(), WindowError> {
+		use winit::window::CursorGrabMode;
+		let result = match config.cursor_grab {
+			CursorGrab::None => self.window.set_cursor_grab(CursorGrabMode::None),
+			CursorGrab::Confined => self.window.set_cursor_grab(CursorGrabMode::Confined),
+			CursorGrab::Locked => self
+				.window
+				.set_cursor_grab(CursorGrabMode::Locked)
+				.or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined)),
+		};
+		result.map_err(WindowError::CursorGrab)?;
+		self.window.set_cursor_visible(config.cursor_visible);
+		Ok(())
+	}
+
+	fn monitor(&self, selection: &MonitorSelection) -> Option<winit::monitor::MonitorHandle> {
+		match selection {
+			MonitorSelection::Current => self.window.current_monitor(),
+			MonitorSelection::Primary => None,
+			MonitorSelection::Index(index) => self.window.available_monitors().nth(*index),
+		}
+		.or_else(|| self.window.primary_monitor())
+	}
+}
+
+fn physical_size(size: [u32; 2]) -> winit::dpi::PhysicalSize<u32> {
+	winit::dpi::PhysicalSize::new(size[0], size[1])
+}
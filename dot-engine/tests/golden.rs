@@ -0,0 +1,15 @@
+//! The golden cases as a test, ignored by default as it needs a Vulkan device. Run it with
+//! `cargo test --test golden -- --ignored`, e.g. with `DOT_DEVICE=llvmpipe` on lavapipe.
+
+#[test]
+#[ignore = "needs a Vulkan device"]
+fn golden_images_match() {
+	let options = dot::golden::Options {
+		tolerance: None,
+		bless: false,
+	};
+	assert!(
+		dot::golden::run(&dot::shaders::modules(), &options),
+		"golden images differ, see target/golden/"
+	);
+}